@@ -1,11 +1,14 @@
-use crupter::hashes::*;
-use hasher::PDHasher;
+use crupter::hashes::encoding::Encoding;
+use crupter::hashes::format::{format_records, HashRecord, OutputFormat};
+use crupter::hashes::multi::Hashes;
+use crupter::utils::io::{set_symlink_policy, SymlinkPolicy};
 use libc::c_char;
 use rayon::prelude::*;
 use std::ffi::{CStr, CString};
+use std::io::Read;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
-use std::io::Read;
+use std::time::{Duration, Instant};
 
 use crate::status_info::StatusInfo;
 use crate::status_read::{FinishedInfo, ReadItem};
@@ -15,85 +18,54 @@ lazy_static! {
         RwLock::new(
             vec![
                 "md5", "sha1", "sha224", "sha256", "sha384", "sha512", "sha3-224", "sha3-256",
-                "sha3-384", "sha3-512",
+                "sha3-384", "sha3-512", "blake2b", "blake2s", "blake3",
             ]
             .iter()
             .map(|name| (*name, true))
             .collect(),
         )
     };
+    static ref HASH_ENCODING: RwLock<Encoding> = RwLock::new(Encoding::default());
+    static ref HASH_HMAC_KEY: RwLock<Option<Vec<u8>>> = RwLock::new(None);
     static ref HASH_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
     static ref HASH_PROGRESS: Mutex<Vec<Arc<RwLock<ReadItem>>>> = Mutex::new(Vec::new());
 }
 
-struct Hashes {
-    names: Vec<&'static str>,
-    hashers: Vec<Box<dyn PDHasher>>,
-}
-
-impl Hashes {
-    pub fn new() -> Self {
-        use digest::Digest;
-        let mut hashers = Vec::<Box<dyn PDHasher>>::new();
-        let mut names = Vec::new();
-        let methods = HASH_METHODS.read().unwrap();
-        for (name, active) in &*methods {
-            if *active {
-                names.push(*name);
-                hashers.push(match *name {
-                    "md5" => Box::new(Md5::new()),
-                    "sha1" => Box::new(Sha1::new()),
-                    "sha224" => Box::new(Sha224::new()),
-                    "sha256" => Box::new(Sha256::new()),
-                    "sha384" => Box::new(Sha384::new()),
-                    "sha512" => Box::new(Sha512::new()),
-                    "sha3-224" => Box::new(Sha3_224::new()),
-                    "sha3-256" => Box::new(Sha3_256::new()),
-                    "sha3-384" => Box::new(Sha3_384::new()),
-                    "sha3-512" => Box::new(Sha3_512::new()),
-                    _ => panic!("unexpected hash method"),
-                });
-            }
-        }
-        Self {
-            names,
-            hashers,
-        }
-    }
+fn new_hashes() -> Hashes {
+    let methods = HASH_METHODS.read().unwrap();
+    let names: Vec<_> = methods
+        .iter()
+        .filter(|(_, active)| *active)
+        .map(|(name, _)| *name)
+        .collect();
+    let key = HASH_HMAC_KEY.read().unwrap();
+    Hashes::new(&names, key.as_deref()).expect("unexpected hash method")
+}
 
-    pub fn consume(&mut self, reader: Arc<RwLock<ReadItem>>) -> Result<(), failure::Error> {
-        // Use a 10mb buffer
-        let mut buffer = vec![0; 10485760];
-        loop {
-            let mut read_guard = reader.write().unwrap();
-            if let &mut ReadItem::File(ref mut reader) = &mut *read_guard {
-                let read_bytes = reader.read(&mut buffer)?;
-                if read_bytes == 0 {
-                    break;
-                } else {
-                    self.hashers.iter_mut().for_each(|val| {
-                        val.consume(&buffer[..read_bytes]);
-                    })
-                }
+fn consume_item(hashes: &mut Hashes, reader: Arc<RwLock<ReadItem>>) -> Result<(), failure::Error> {
+    // Use a 10mb buffer
+    let mut buffer = vec![0; 10485760];
+    loop {
+        let mut read_guard = reader.write().unwrap();
+        if let &mut ReadItem::File(ref mut reader) = &mut *read_guard {
+            let read_bytes = reader.read(&mut buffer)?;
+            if read_bytes == 0 {
+                break;
+            } else {
+                hashes.consume(&buffer[..read_bytes]);
             }
         }
-        Ok(())
     }
+    Ok(())
+}
 
-    pub fn produce(self) -> String {
-        self.names
-            .iter()
-            .zip(self.hashers.into_iter())
-            .map(|(key, val)| {
-                format!(
-                    "{}: {}",
-                    key,
-                    val.produce().unwrap_or_else(|e| e.to_string())
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    }
+fn produce_text(digests: &[(String, Vec<u8>)]) -> String {
+    let encoding = *HASH_ENCODING.read().unwrap();
+    digests
+        .iter()
+        .map(|(key, val)| format!("{}: {}", key, encoding.encode(val)))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[no_mangle]
@@ -107,6 +79,57 @@ pub extern "C" fn add_hash_file(path_raw: *const c_char) -> u32 {
     files.len() as u32
 }
 
+/// Set the text encoding of digests (hex, upper-hex, base64, base64url or base32),
+/// returns false for an unknown encoding
+#[no_mangle]
+pub extern "C" fn set_hash_encoding(encoding_raw: *const c_char) -> bool {
+    let c_str = unsafe {
+        assert!(!encoding_raw.is_null());
+        CStr::from_ptr(encoding_raw)
+    };
+    match c_str.to_string_lossy().parse::<Encoding>() {
+        Ok(encoding) => {
+            *HASH_ENCODING.write().unwrap() = encoding;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Compute HMAC keyed with the `len` bytes at `key_raw` in following sessions
+#[no_mangle]
+pub extern "C" fn set_hash_hmac_key(key_raw: *const u8, len: usize) {
+    let key = unsafe {
+        assert!(!key_raw.is_null());
+        std::slice::from_raw_parts(key_raw, len)
+    };
+    *HASH_HMAC_KEY.write().unwrap() = Some(key.to_vec());
+}
+
+/// Set how symbolic links are treated in following sessions: "arguments" or "follow"
+/// to hash the files they point to, "no-follow" to hash the path they point to.
+/// Returns false for an unknown policy
+#[no_mangle]
+pub extern "C" fn set_hash_symlinks(policy_raw: *const c_char) -> bool {
+    let c_str = unsafe {
+        assert!(!policy_raw.is_null());
+        CStr::from_ptr(policy_raw)
+    };
+    match c_str.to_string_lossy().parse::<SymlinkPolicy>() {
+        Ok(policy) => {
+            set_symlink_policy(policy);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Go back to plain hashing in following sessions
+#[no_mangle]
+pub extern "C" fn clear_hash_hmac_key() {
+    *HASH_HMAC_KEY.write().unwrap() = None;
+}
+
 #[no_mangle]
 pub extern "C" fn clean_hash_files() {
     let mut files = HASH_FILES.lock().unwrap();
@@ -144,18 +167,27 @@ pub extern "C" fn run_hash_session() {
                 let name = read.name.clone();
                 let bytes = read.bytes;
                 drop(read_guard);
-                let mut hashes = Hashes::new();
-                if let Err(err) = hashes.consume(reader.clone()) {
+                let start = Instant::now();
+                let mut hashes = new_hashes();
+                if let Err(err) = consume_item(&mut hashes, reader.clone()) {
                     let mut read_guard = reader.write().unwrap();
                     *read_guard = ReadItem::Failed(name, err);
                 } else {
                     let mut read_guard = reader.write().unwrap();
-                    let result = hashes.produce();
+                    // the length of a FIFO is only known once it is read
+                    let bytes = match &*read_guard {
+                        ReadItem::File(read) => read.current,
+                        _ => bytes,
+                    };
+                    let digests = hashes.digests();
+                    let result = produce_text(&digests);
                     println!("{}", result);
                     *read_guard = ReadItem::Done(FinishedInfo {
                         name,
                         bytes,
                         result,
+                        digests,
+                        elapsed: start.elapsed(),
                     });
                 }
             }
@@ -200,6 +232,59 @@ pub extern "C" fn get_hash_progress(index: u32) -> StatusInfo {
     }
 }
 
+/// Report every finished item of the session in the given format
+/// (gnu, bsd, sfv, json or csv), returns null for an unknown format
+#[no_mangle]
+pub extern "C" fn get_hash_report(format_raw: *const c_char) -> *mut c_char {
+    let c_str = unsafe {
+        assert!(!format_raw.is_null());
+        CStr::from_ptr(format_raw)
+    };
+    let format = match c_str.to_string_lossy().parse::<OutputFormat>() {
+        Ok(format) => format,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let keyed = HASH_HMAC_KEY.read().unwrap().is_some();
+    let methods: Vec<_> = HASH_METHODS
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|(_, active)| *active)
+        .map(|(name, _)| match keyed {
+            true => format!("hmac-{}", name),
+            false => name.to_string(),
+        })
+        .collect();
+    let progresses = HASH_PROGRESS.lock().unwrap();
+    let mut records = vec![];
+    for item in progresses.iter() {
+        match &*item.read().unwrap() {
+            ReadItem::Done(info) => {
+                records.extend(info.digests.iter().map(|(name, digest)| HashRecord {
+                    path: Some(PathBuf::from(&info.name)),
+                    algorithm: name.clone(),
+                    result: Ok(digest.clone()),
+                    size: Some(info.bytes),
+                    elapsed: info.elapsed,
+                }))
+            }
+            ReadItem::Failed(name, err) => records.push(HashRecord {
+                path: Some(PathBuf::from(name)),
+                algorithm: methods.join(","),
+                result: Err(err.to_string()),
+                size: None,
+                elapsed: Duration::default(),
+            }),
+            // still running
+            ReadItem::File(_) => {}
+        }
+    }
+    let encoding = *HASH_ENCODING.read().unwrap();
+    CString::new(format_records(format, encoding, &records))
+        .unwrap()
+        .into_raw()
+}
+
 #[no_mangle]
 pub extern "C" fn hash_running_count() -> u32 {
     let progresses = HASH_PROGRESS.lock().unwrap();
@@ -1,12 +1,15 @@
-use crupter::utils::errors::ReadError;
+use crupter::utils::io::{self, ByteRange, Input};
 use std::io::Read;
 
-pub type FileRead = StatusRead<std::io::BufReader<std::fs::File>>;
+pub type FileRead = StatusRead<Input>;
 
 pub struct FinishedInfo {
     pub name: String,
     pub result: String,
     pub bytes: u64,
+    /// Digest bytes of each algorithm
+    pub digests: Vec<(String, Vec<u8>)>,
+    pub elapsed: std::time::Duration,
 }
 pub enum ReadItem {
     File(FileRead),
@@ -26,7 +29,7 @@ pub enum ReadState {
 pub struct StatusRead<R: Read> {
     /// Underlying Read Object
     inner: R,
-    /// Total bytes to read
+    /// Total bytes to read, 0 for FIFOs which are read up to their end
     pub bytes: u64,
     /// Current bytes have been read
     pub current: u64,
@@ -53,20 +56,13 @@ impl ReadItem {
 }
 
 impl FileRead {
+    /// Open a regular file, block device or FIFO, see `io::open_range`
     pub fn from_file_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, failure::Error> {
-        let file = std::fs::File::open(&path).map_err(|err| ReadError::OpenFileError { err })?;
+        let (input, length) = io::open_range(path.as_ref(), ByteRange::default())?;
         let filename = path.as_ref().file_name().unwrap();
-        let meta = file
-            .metadata()
-            .map_err(|err| ReadError::MetadataError { err })?;
-        if !meta.is_file() {
-            Err(ReadError::NotFileError)?;
-        }
-        let file_size = meta.len();
-        let buf_file = std::io::BufReader::new(file);
         Ok(StatusRead {
-            inner: buf_file,
-            bytes: file_size,
+            inner: input,
+            bytes: length.unwrap_or(0),
             current: 0,
             state: ReadState::Going,
             name: filename.to_string_lossy().to_string(),
@@ -84,7 +80,7 @@ impl<R: Read> Read for StatusRead<R> {
             err
         })?;
         self.current += read_size as u64;
-        if self.current >= self.bytes {
+        if read_size == 0 || (self.bytes > 0 && self.current >= self.bytes) {
             self.state = ReadState::Finished;
         }
         Ok(read_size)
@@ -1,29 +1,605 @@
-use crate::utils::errors::ReadError;
+use crate::hashes::database::{Attribute, DbCommand};
+use crate::hashes::dupes::DupesAction;
+use crate::hashes::encoding::Encoding;
+use crate::hashes::format::OutputFormat;
+use crate::hashes::git::ObjectFormat;
+use crate::hashes::mtree;
+use crate::hashes::stored::ChecksumStore;
+use crate::utils::errors::{ArgumentError, ReadError};
+use crate::utils::filter::FileFilter;
+use crate::utils::io::{ByteRange, IoMode, SymlinkPolicy};
 use crate::utils::mustache;
+use crate::utils::walk;
 use clap::ArgMatches;
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Key given by `--hmac-key` or read from `--hmac-key-file`
+fn read_hmac_key(matches: &ArgMatches) -> Result<Option<Vec<u8>>, failure::Error> {
+    if let Some(key) = matches.value_of("hmac-key") {
+        Ok(Some(key.as_bytes().to_vec()))
+    } else if let Some(key_file) = matches.value_of("hmac-key-file") {
+        let key = std::fs::read(key_file).map_err(|err| ReadError::OpenFileError { err })?;
+        Ok(Some(key))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Split a list of file names on NULs, or on newlines unless `null`, skipping empty names
+fn split_file_list(list: &[u8], null: bool) -> Vec<PathBuf> {
+    let separator = if null { b'\0' } else { b'\n' };
+    list.split(|byte| *byte == separator)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                PathBuf::from(std::ffi::OsStr::from_bytes(name))
+            }
+            #[cfg(not(unix))]
+            {
+                PathBuf::from(String::from_utf8_lossy(name).into_owned())
+            }
+        })
+        .collect()
+}
+
+/// File names given as arguments followed by those listed in the `--files-from` file,
+/// `-` reading the list from the standard input. None when there are neither,
+/// each subcommand then has its own default
+fn read_filenames(matches: &ArgMatches) -> Result<Option<Vec<PathBuf>>, failure::Error> {
+    let mut filenames: Vec<_> = matches
+        .values_of_os("file")
+        .map(|files| files.map(PathBuf::from).collect())
+        .unwrap_or_default();
+    let list_file = match matches.value_of_os("files-from") {
+        Some(list_file) => list_file,
+        None if filenames.is_empty() => return Ok(None),
+        None => return Ok(Some(filenames)),
+    };
+    let list = if list_file == "-" {
+        let mut list = vec![];
+        std::io::stdin().lock().read_to_end(&mut list)?;
+        list
+    } else {
+        std::fs::read(list_file).map_err(|err| ReadError::OpenFileError { err })?
+    };
+    filenames.extend(split_file_list(&list, matches.is_present("null")));
+    if filenames.is_empty() {
+        Err(ArgumentError::NoFileNames {
+            file: list_file.to_string_lossy().into_owned(),
+        })?;
+    }
+    Ok(Some(filenames))
+}
+
+/// File names as `read_filenames` gives them, without those left out by `filter`
+fn read_filtered_filenames(
+    matches: &ArgMatches,
+    filter: &FileFilter,
+) -> Result<Option<Vec<PathBuf>>, failure::Error> {
+    match read_filenames(matches)? {
+        Some(filenames) => {
+            let selected = filter.select(filenames);
+            if selected.is_empty() {
+                Err(ArgumentError::AllFilesFiltered)?;
+            }
+            Ok(Some(selected))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Filter built from `--include`, `--exclude`, `--min-size`, `--newer-than` and `--no-ignore`
+fn read_filter(matches: &ArgMatches) -> Result<FileFilter, failure::Error> {
+    let patterns = |name| -> Vec<String> {
+        matches
+            .values_of(name)
+            .map(|patterns| patterns.map(String::from).collect())
+            .unwrap_or_default()
+    };
+    FileFilter::new(
+        &patterns("include"),
+        &patterns("exclude"),
+        matches.value_of("min-size").map(parse_size).transpose()?,
+        matches.value_of("newer-than").map(parse_time).transpose()?,
+        !matches.is_present("no-ignore"),
+    )
+}
+
+/// Policy selected by `--follow-symlinks` or `--no-follow`
+fn read_symlink_policy(matches: &ArgMatches) -> SymlinkPolicy {
+    if matches.is_present("follow-symlinks") {
+        SymlinkPolicy::Follow
+    } else if matches.is_present("no-follow") {
+        SymlinkPolicy::NoFollow
+    } else {
+        SymlinkPolicy::Arguments
+    }
+}
+
+/// Days from 1970-01-01 to a date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Number of days in a month of the proleptic Gregorian calendar
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parse a UTC date `YYYY-MM-DD`, optionally followed by ` HH:MM[:SS]` or `THH:MM[:SS]`
+fn parse_date(value: &str) -> Option<SystemTime> {
+    let (date, time) = match value.find(['T', ' ']) {
+        Some(at) => (&value[..at], Some(&value[at + 1..])),
+        None => (value, None),
+    };
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    let time: Vec<i64> = match time {
+        Some(time) => time
+            .split(':')
+            .map(|field| field.parse().ok())
+            .collect::<Option<_>>()?,
+        None => vec![0, 0],
+    };
+    let (year, month, day) = match date.as_slice() {
+        [year, month, day]
+            if (1..=12).contains(month) && (1..=days_in_month(*year, *month)).contains(day) =>
+        {
+            (*year, *month, *day)
+        }
+        _ => return None,
+    };
+    let (hours, minutes, seconds) = match time.as_slice() {
+        [hours, minutes] => (*hours, *minutes, 0),
+        [hours, minutes, seconds] => (*hours, *minutes, *seconds),
+        _ => return None,
+    };
+    if !(0..24).contains(&hours) || !(0..60).contains(&minutes) || !(0..60).contains(&seconds) {
+        return None;
+    }
+    let seconds =
+        days_from_civil(year, month, day) * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+/// Parse a point in time: an age such as `30m`, `12h`, `7d` or `2w` before now,
+/// a UTC date as accepted by `parse_date`, or a file whose modification time is taken
+pub fn parse_time(value: &str) -> Result<SystemTime, failure::Error> {
+    let invalid = || ArgumentError::InvalidTime {
+        value: value.to_owned(),
+    };
+    if let Some((at, unit)) = value.char_indices().last() {
+        let unit_seconds = match unit {
+            's' => Some(1),
+            'm' => Some(60),
+            'h' => Some(3_600),
+            'd' => Some(86_400),
+            'w' => Some(604_800),
+            _ => None,
+        };
+        if let (Some(unit_seconds), Ok(count)) = (unit_seconds, value[..at].parse::<u64>()) {
+            let age = count.checked_mul(unit_seconds).ok_or_else(invalid)?;
+            return Ok(SystemTime::now()
+                .checked_sub(Duration::from_secs(age))
+                .ok_or_else(invalid)?);
+        }
+    }
+    if let Some(time) = parse_date(value) {
+        return Ok(time);
+    }
+    Ok(std::fs::metadata(value)
+        .and_then(|meta| meta.modified())
+        .map_err(|_| invalid())?)
+}
+
+/// Parse a byte count such as `4096`, `64K` or `1M`, suffixes are powers of 1024
+pub fn parse_size(value: &str) -> Result<u64, failure::Error> {
+    let invalid = || ArgumentError::InvalidSize {
+        value: value.to_owned(),
+    };
+    let (number, shift) = match value.char_indices().last() {
+        Some((at, suffix)) if suffix.is_ascii_alphabetic() => {
+            let shift = match suffix.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => Err(invalid())?,
+            };
+            (&value[..at], shift)
+        }
+        _ => (value, 0),
+    };
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    Ok(number
+        .checked_shl(shift)
+        .filter(|size| size >> shift == number)
+        .ok_or_else(invalid)?)
+}
+
+/// Parse an output length in bytes, or in bits when suffixed with `bits`
+pub fn parse_output_length(value: &str) -> Result<usize, failure::Error> {
+    let invalid = || ArgumentError::InvalidOutputLength {
+        value: value.to_owned(),
+    };
+    let length = match value
+        .strip_suffix("bits")
+        .or_else(|| value.strip_suffix("bit"))
+    {
+        Some(bits) => {
+            let bits = bits.trim_end().parse::<usize>().map_err(|_| invalid())?;
+            if bits % 8 != 0 {
+                Err(invalid())?;
+            }
+            bits / 8
+        }
+        None => value.parse::<usize>().map_err(|_| invalid())?,
+    };
+    if length == 0 {
+        Err(invalid())?;
+    }
+    Ok(length)
+}
 
 #[derive(Debug)]
 pub struct HashArgs {
     pub filenames: Vec<PathBuf>,
     pub parallels: u32,
     pub silent: bool,
+    /// How files are read
+    pub io: IoMode,
+    /// Read checksums from the files and verify them
+    pub check: bool,
+    /// Hash every file below the given directories
+    pub recursive: bool,
+    /// Print a single Merkle tree digest for each directory
+    pub tree: bool,
+    /// Include permission bits in the tree digest
+    pub tree_modes: bool,
+    /// Output format, the crupter format when None
+    pub format: Option<OutputFormat>,
+    /// Text encoding of digests
+    pub encoding: Encoding,
+    /// Key to compute HMAC with
+    pub hmac_key: Option<Vec<u8>>,
+    /// File of cached digests to reuse for unchanged files
+    pub cache: Option<PathBuf>,
+    /// Hash every file again, refreshing the cache
+    pub rehash: bool,
+    /// Also output a digest for every block of this many bytes
+    pub block_size: Option<u64>,
+    /// Compare two block lists and report the differing byte ranges
+    pub compare: bool,
+    /// Part of each file to hash
+    pub range: ByteRange,
+    /// Output length in bytes of extendable-output functions
+    pub output_length: Option<usize>,
+    /// cSHAKE function name
+    pub function_name: String,
+    /// cSHAKE customization string
+    pub customization: String,
+    /// File the hasher state is saved to while hashing
+    pub checkpoint: Option<PathBuf>,
+    /// Continue from the checkpoint when there is one
+    pub resume: bool,
+    /// Bytes hashed between two saves of the checkpoint
+    pub checkpoint_interval: u64,
+    /// Files selected in directories and among the given files
+    pub filter: FileFilter,
+    /// How symbolic links are treated
+    pub symlinks: SymlinkPolicy,
+    /// Where the checksum of every hashed file is also written
+    pub stores: Vec<ChecksumStore>,
+    /// Verify the checksums found in `stores` instead of writing them
+    pub scrub: bool,
 }
 
 impl TryFrom<&ArgMatches<'_>> for HashArgs {
     type Error = failure::Error;
     fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
-        let filenames = if let Some(files) = matches.values_of("file") {
-            files.map(PathBuf::from).collect()
-        } else {
-            vec![]
-        };
+        let filter = read_filter(matches)?;
+        let filenames = read_filtered_filenames(matches, &filter)?.unwrap_or_default();
         let parallels = matches.value_of("parallels").unwrap().parse::<u32>()?;
+        let mut stores = vec![];
+        if matches.is_present("sidecar") {
+            stores.push(ChecksumStore::Sidecar);
+        }
+        if matches.is_present("xattr") {
+            stores.push(ChecksumStore::Xattr);
+        }
         Ok(Self {
             filenames,
             parallels,
             silent: matches.is_present("silent"),
+            io: matches.value_of("io").unwrap().parse()?,
+            check: matches.is_present("check"),
+            recursive: matches.is_present("recursive"),
+            tree: matches.is_present("tree"),
+            tree_modes: matches.is_present("tree-modes"),
+            format: matches.value_of("format").map(str::parse).transpose()?,
+            encoding: matches.value_of("encoding").unwrap().parse()?,
+            hmac_key: read_hmac_key(matches)?,
+            cache: matches.value_of("cache").map(PathBuf::from),
+            rehash: matches.is_present("rehash"),
+            block_size: matches.value_of("block-size").map(parse_size).transpose()?,
+            compare: matches.is_present("compare"),
+            range: ByteRange {
+                offset: matches
+                    .value_of("offset")
+                    .map(parse_size)
+                    .transpose()?
+                    .unwrap_or(0),
+                length: matches.value_of("length").map(parse_size).transpose()?,
+            },
+            output_length: matches
+                .value_of("output-length")
+                .map(parse_output_length)
+                .transpose()?,
+            function_name: matches.value_of("function-name").unwrap_or("").to_owned(),
+            customization: matches.value_of("customization").unwrap_or("").to_owned(),
+            checkpoint: matches.value_of("checkpoint").map(PathBuf::from),
+            resume: matches.is_present("resume"),
+            checkpoint_interval: parse_size(matches.value_of("checkpoint-interval").unwrap())?,
+            filter,
+            symlinks: read_symlink_policy(matches),
+            stores,
+            scrub: matches.is_present("scrub"),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MultiHashArgs {
+    pub filenames: Vec<PathBuf>,
+    pub algorithms: Vec<String>,
+    pub parallels: u32,
+    pub silent: bool,
+    /// How files are read
+    pub io: IoMode,
+    /// Output format, the crupter format when None
+    pub format: Option<OutputFormat>,
+    /// Text encoding of digests
+    pub encoding: Encoding,
+    /// Key to compute HMAC with
+    pub hmac_key: Option<Vec<u8>>,
+    /// How symbolic links are treated
+    pub symlinks: SymlinkPolicy,
+}
+
+impl TryFrom<&ArgMatches<'_>> for MultiHashArgs {
+    type Error = failure::Error;
+    fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
+        let filter = read_filter(matches)?;
+        let filenames = read_filtered_filenames(matches, &filter)?.unwrap_or_default();
+        let algorithms = matches
+            .values_of("algorithms")
+            .unwrap()
+            .map(String::from)
+            .collect();
+        let parallels = matches.value_of("parallels").unwrap().parse::<u32>()?;
+        Ok(Self {
+            filenames,
+            algorithms,
+            parallels,
+            silent: matches.is_present("silent"),
+            io: matches.value_of("io").unwrap().parse()?,
+            format: matches.value_of("format").map(str::parse).transpose()?,
+            encoding: matches.value_of("encoding").unwrap().parse()?,
+            hmac_key: read_hmac_key(matches)?,
+            symlinks: read_symlink_policy(matches),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct AuditArgs {
+    /// Manifest or directory taken as the reference
+    pub old: PathBuf,
+    /// Manifest or directory compared against the reference
+    pub new: PathBuf,
+    /// Algorithm used to hash directories
+    pub algorithm: String,
+    pub parallels: u32,
+    pub silent: bool,
+    /// How files are read
+    pub io: IoMode,
+    /// Print a JSON report instead of the human readable one
+    pub json: bool,
+    /// List unchanged entries as well
+    pub verbose: bool,
+    /// Text encoding of digests in manifests
+    pub encoding: Encoding,
+}
+
+impl TryFrom<&ArgMatches<'_>> for AuditArgs {
+    type Error = failure::Error;
+    fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
+        let parallels = matches.value_of("parallels").unwrap().parse::<u32>()?;
+        Ok(Self {
+            old: PathBuf::from(matches.value_of("old").unwrap()),
+            new: PathBuf::from(matches.value_of("new").unwrap()),
+            algorithm: matches.value_of("algorithm").unwrap().to_owned(),
+            parallels,
+            silent: matches.is_present("silent"),
+            io: matches.value_of("io").unwrap().parse()?,
+            json: matches.is_present("json"),
+            verbose: matches.is_present("verbose"),
+            encoding: matches.value_of("encoding").unwrap().parse()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DupesArgs {
+    /// Files and directories searched for duplicates
+    pub paths: Vec<PathBuf>,
+    /// Algorithm used to compare contents
+    pub algorithm: String,
+    pub parallels: u32,
+    pub silent: bool,
+    /// How files are read
+    pub io: IoMode,
+    /// Print a JSON report instead of the human readable one
+    pub json: bool,
+    /// Text encoding of digests
+    pub encoding: Encoding,
+    /// What to do with the duplicates, only reported when None
+    pub action: Option<DupesAction>,
+    /// Delete without asking for confirmation
+    pub yes: bool,
+    /// Files selected in directories and among the given files
+    pub filter: FileFilter,
+}
+
+impl TryFrom<&ArgMatches<'_>> for DupesArgs {
+    type Error = failure::Error;
+    fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
+        let parallels = matches.value_of("parallels").unwrap().parse::<u32>()?;
+        let filter = read_filter(matches)?;
+        let paths = matches
+            .values_of_os("path")
+            .unwrap()
+            .map(PathBuf::from)
+            .collect();
+        let action = if matches.is_present("hardlink") {
+            Some(DupesAction::Hardlink)
+        } else if matches.is_present("delete") {
+            Some(DupesAction::Delete)
+        } else {
+            None
+        };
+        Ok(Self {
+            paths: filter.select(paths),
+            algorithm: matches.value_of("algorithm").unwrap().to_owned(),
+            parallels,
+            silent: matches.is_present("silent"),
+            io: matches.value_of("io").unwrap().parse()?,
+            json: matches.is_present("json"),
+            encoding: matches.value_of("encoding").unwrap().parse()?,
+            action,
+            yes: matches.is_present("yes"),
+            filter,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DbArgs {
+    pub command: DbCommand,
+    /// The database file
+    pub database: PathBuf,
+    /// Tree to record or compare, the recorded root when None
+    pub root: Option<PathBuf>,
+    /// Attributes recorded by `init`
+    pub attributes: Vec<Attribute>,
+    /// Algorithms recorded by `init`
+    pub algorithms: Vec<String>,
+    /// Key the database is signed with
+    pub hmac_key: Option<Vec<u8>>,
+    pub parallels: u32,
+    pub silent: bool,
+    /// How files are read
+    pub io: IoMode,
+}
+
+impl TryFrom<&ArgMatches<'_>> for DbArgs {
+    type Error = failure::Error;
+    fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
+        let (command, matches) = match matches.subcommand() {
+            ("init", Some(matches)) => (DbCommand::Init, matches),
+            ("check", Some(matches)) => (DbCommand::Check, matches),
+            ("update", Some(matches)) => (DbCommand::Update, matches),
+            _ => unreachable!(),
+        };
+        let attributes = matches
+            .values_of("attributes")
+            .map(|names| names.map(str::parse).collect::<Result<Vec<_>, _>>())
+            .transpose()?
+            .unwrap_or_default();
+        let algorithms = matches
+            .values_of("algorithms")
+            .map(|names| names.map(String::from).collect())
+            .unwrap_or_default();
+        Ok(Self {
+            command,
+            database: PathBuf::from(matches.value_of_os("database").unwrap()),
+            root: matches.value_of_os("dir").map(PathBuf::from),
+            attributes,
+            algorithms,
+            hmac_key: read_hmac_key(matches)?,
+            parallels: matches.value_of("parallels").unwrap().parse::<u32>()?,
+            silent: matches.is_present("silent"),
+            io: matches.value_of("io").unwrap().parse()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MtreeArgs {
+    /// Tree described by the specification
+    pub root: PathBuf,
+    /// Specification to verify the tree against, the specification is written when None
+    pub spec: Option<PathBuf>,
+    /// Keywords written to the specification, with canonical names
+    pub keywords: Vec<String>,
+    pub parallels: u32,
+    pub silent: bool,
+    /// How files are read
+    pub io: IoMode,
+}
+
+impl TryFrom<&ArgMatches<'_>> for MtreeArgs {
+    type Error = failure::Error;
+    fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
+        let keywords: Vec<_> = matches.values_of("keywords").unwrap().collect();
+        Ok(Self {
+            root: PathBuf::from(matches.value_of_os("dir").unwrap()),
+            spec: matches.value_of_os("file").map(PathBuf::from),
+            keywords: mtree::parse_keywords(&keywords)?,
+            parallels: matches.value_of("parallels").unwrap().parse::<u32>()?,
+            silent: matches.is_present("silent"),
+            io: matches.value_of("io").unwrap().parse()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct GitHashArgs {
+    /// Files hashed as blobs and directories hashed as trees, - for the standard input
+    pub paths: Vec<PathBuf>,
+    /// Hash function of the object IDs
+    pub format: ObjectFormat,
+    pub parallels: u32,
+}
+
+impl TryFrom<&ArgMatches<'_>> for GitHashArgs {
+    type Error = failure::Error;
+    fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
+        Ok(Self {
+            paths: matches
+                .values_of_os("path")
+                .unwrap()
+                .map(PathBuf::from)
+                .collect(),
+            format: matches.value_of("object-format").unwrap().parse()?,
+            parallels: matches.value_of("parallels").unwrap().parse::<u32>()?,
         })
     }
 }
@@ -37,6 +613,8 @@ pub struct CipherArgs {
     pub parallels: u32,
     pub silent: bool,
     pub list_name: bool,
+    /// How files are read
+    pub io: IoMode,
 }
 
 impl TryFrom<&ArgMatches<'_>> for CipherArgs {
@@ -69,7 +647,8 @@ impl TryFrom<&ArgMatches<'_>> for CipherArgs {
         let output_dir = std::path::Path::new(&template_str)
             .parent()
             .unwrap_or(std::path::Path::new(&template_str));
-        if output_dir.as_os_str() != "" && !output_dir.exists() && !matches.is_present("list-name") {
+        if output_dir.as_os_str() != "" && !output_dir.exists() && !matches.is_present("list-name")
+        {
             std::fs::create_dir(output_dir).map_err(|err| ReadError::CreateDirError {
                 err,
                 dir: output_dir.to_string_lossy().to_string(),
@@ -78,16 +657,10 @@ impl TryFrom<&ArgMatches<'_>> for CipherArgs {
         }
         let output_template = mustache::compile_mustache(&template_str, false)?;
         let parallels = matches.value_of("parallels").unwrap().parse::<u32>()?;
-        let filenames = if let Some(files) = matches.values_of("file") {
-            files.map(PathBuf::from).collect()
-        } else {
-            std::fs::read_dir(".")
-                .map_err(|err| ReadError::ReadDirError {
-                    err,
-                    dir: ".".to_owned(),
-                })?
-                .map(|entry| Ok(entry?.path()))
-                .collect::<Result<Vec<_>, std::io::Error>>()?
+        let filter = read_filter(matches)?;
+        let filenames = match read_filtered_filenames(matches, &filter)? {
+            Some(files) => files,
+            None => walk::list_files(Path::new("."), &filter)?,
         };
         let list_name = matches.is_present("list-name");
         Ok(Self {
@@ -98,6 +671,7 @@ impl TryFrom<&ArgMatches<'_>> for CipherArgs {
             parallels,
             silent: matches.is_present("silent") || (decrypt && list_name),
             list_name,
+            io: matches.value_of("io").unwrap().parse()?,
         })
     }
 }
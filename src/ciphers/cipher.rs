@@ -1,11 +1,12 @@
 use crate::ciphers::passphrase;
 use crate::utils::errors::CipherError;
+use crate::utils::io;
 use crate::utils::mustache;
 use generic_array::typenum::Unsigned;
 use mustache::MustacheExp;
+use ofb::cipher::{NewCipher, StreamCipher};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use ofb::cipher::{NewCipher, StreamCipher};
 
 pub trait CrupterCipher {
     fn apply<R: Read, W: Write>(&mut self, reader: R, writer: W) -> Result<usize, failure::Error>;
@@ -31,8 +32,7 @@ impl<S: NewCipher + StreamCipher> CrupterCipher for S {
         mut reader: R,
         mut writer: W,
     ) -> Result<usize, failure::Error> {
-        // Use a 10mb buffer
-        let mut buffer = vec![0; 10485760];
+        let mut buffer = io::buffer();
         let mut total_bytes = 0;
         loop {
             let read_bytes = reader.read(&mut buffer)?;
@@ -101,7 +101,8 @@ impl<S: NewCipher + StreamCipher> CrupterCipher for S {
         let mut key_iv = vec![0; key_len + iv_len];
         file_reader.read_exact(&mut key_iv)?;
         self.apply_keystream(&mut key_iv);
-        let mut file_cipher = Self::new_from_slices(&key_iv[..key_len], &key_iv[key_len..]).unwrap();
+        let mut file_cipher =
+            Self::new_from_slices(&key_iv[..key_len], &key_iv[key_len..]).unwrap();
         // Get filename
         let mut filename_len_buf = [0; 4];
         file_reader.read_exact(&mut filename_len_buf)?;
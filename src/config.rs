@@ -21,11 +21,24 @@ impl Command {
             handler,
         }
     }
+    fn new_tool(
+        name: &str,
+        help_msg: &str,
+        handler: fn(&ArgMatches) -> Result<(), failure::Error>,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            help_msg: help_msg.to_owned(),
+            handler,
+        }
+    }
 }
 
 pub struct Config {
     pub checksums: Vec<Command>,
     pub ciphers: Vec<Command>,
+    /// Commands with their own set of arguments
+    pub tools: Vec<Command>,
 }
 
 pub fn init() -> Config {
@@ -40,10 +53,24 @@ pub fn init() -> Config {
             ("sha2-256", handlers::sha2_256_handler),
             ("sha2-384", handlers::sha2_384_handler),
             ("sha2-512", handlers::sha2_512_handler),
+            ("sha2-256-tree", handlers::sha2_256_tree_handler),
             ("sha3-224", handlers::sha3_224_handler),
             ("sha3-256", handlers::sha3_256_handler),
             ("sha3-384", handlers::sha3_384_handler),
             ("sha3-512", handlers::sha3_512_handler),
+            ("shake128", handlers::shake128_handler),
+            ("shake256", handlers::shake256_handler),
+            ("cshake128", handlers::cshake128_handler),
+            ("cshake256", handlers::cshake256_handler),
+            ("blake2b", handlers::blake2b_handler),
+            ("blake2s", handlers::blake2s_handler),
+            ("blake3", handlers::blake3_handler),
+            ("crc32", handlers::crc32_handler),
+            ("crc32c", handlers::crc32c_handler),
+            ("crc64", handlers::crc64_handler),
+            ("xxh3", handlers::xxh3_handler),
+            ("xxh128", handlers::xxh128_handler),
+            ("adler32", handlers::adler32_handler),
         ]
         .iter()
         .map(|(n, handler)| Command::new_checksum(n, *handler))
@@ -59,5 +86,37 @@ pub fn init() -> Config {
         .iter()
         .map(|(n, handler)| Command::new_cipher(n, *handler))
         .collect(),
+        tools: vec![
+            Command::new_tool(
+                "hash",
+                "Get checksums of several algorithms in a single read.",
+                handlers::multi_hash_handler,
+            ),
+            Command::new_tool(
+                "audit",
+                "Compare manifests or directories: unchanged, modified, added, removed and moved files.",
+                handlers::audit_handler,
+            ),
+            Command::new_tool(
+                "dupes",
+                "Find duplicate files and the space they waste, optionally hard linking or deleting them.",
+                handlers::dupes_handler,
+            ),
+            Command::new_tool(
+                "db",
+                "Keep an integrity database of a tree: init, check and update it.",
+                handlers::db_handler,
+            ),
+            Command::new_tool(
+                "mtree",
+                "Print a BSD mtree specification of a tree, or verify a tree against one.",
+                handlers::mtree_handler,
+            ),
+            Command::new_tool(
+                "git-hash",
+                "Get the Git object IDs of files and directories, as git hash-object and git write-tree.",
+                handlers::git_hash_handler,
+            ),
+        ],
     }
 }
@@ -1,28 +1,1002 @@
-use crate::args::{CipherArgs, HashArgs};
+use crate::args::{
+    AuditArgs, CipherArgs, DbArgs, DupesArgs, GitHashArgs, HashArgs, MtreeArgs, MultiHashArgs,
+};
 use crate::ciphers::{cipher::CrupterCipher, passphrase};
-use crate::hashes::hasher::Hasher;
+use crate::hashes::audit;
+use crate::hashes::blake;
+use crate::hashes::blocks::{self, BlockList, Blockwise};
+use crate::hashes::cache::{FileStamp, HashCache};
+use crate::hashes::checkpoint::Checkpoint;
+use crate::hashes::database::{Database, DbCommand, Drift};
+use crate::hashes::dupes::{self, DupesAction, DuplicateSet};
+use crate::hashes::encoding::Encoding;
+use crate::hashes::format::{self, HashRecord, OutputFormat};
+use crate::hashes::hasher::{Checksum, Hasher, Resumable};
+use crate::hashes::hmac::HmacChecksum;
+use crate::hashes::mtree::{self, Node};
+use crate::hashes::multi::{self, Concatenated, Hashes};
+use crate::hashes::registry::{self, ALGORITHMS};
+use crate::hashes::sha256_tree::{self, Sha256Tree};
+use crate::hashes::sha2_state::{Sha256State, Sha512State};
+use crate::hashes::stored::{self, ChecksumStore, ScrubStatus, ScrubSummary};
+use crate::hashes::sums;
+use crate::hashes::tree::{self, NodeKind};
+use crate::hashes::xof::{Xof, SHAKE128_DEFAULT_LENGTH, SHAKE256_DEFAULT_LENGTH};
+use crate::utils::errors::{ArgumentError, ChecksumError, ReadError};
+use crate::utils::io::{self, is_stdin, set_io_mode, set_symlink_policy, stdin_range, ByteRange};
 use crate::utils::mustache;
 use crate::utils::parallel::set_num_threads;
 use crate::utils::progress_read::{prepare_multi_bar, ProgressRead};
+use crate::utils::walk;
 use clap::ArgMatches;
-use digest::Digest;
+use digest::{BlockInput, FixedOutput, Input, Reset};
+use generic_array::typenum::Unsigned;
+use generic_array::ArrayLength;
+use indicatif::ProgressBar;
 use ofb::cipher::{NewCipher, StreamCipher};
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use std::convert::TryFrom;
 
-fn hash_handler<D: Digest>(matches: &ArgMatches) -> Result<(), failure::Error> {
-    let args = HashArgs::try_from(matches)?;
+fn check_handler<C: Checksum, F: Fn() -> C + Sync>(
+    args: &HashArgs,
+    new_hasher: &F,
+) -> Result<(), failure::Error> {
+    let sums_files = if args.filenames.is_empty() {
+        vec![(
+            "standard input".to_owned(),
+            sums::parse_sums(std::io::stdin().lock(), args.encoding)?,
+        )]
+    } else {
+        args.filenames
+            .iter()
+            .map(|file| {
+                if is_stdin(file) {
+                    return Ok((
+                        "standard input".to_owned(),
+                        sums::parse_sums(std::io::stdin().lock(), args.encoding)?,
+                    ));
+                }
+                let reader =
+                    std::fs::File::open(file).map_err(|err| ReadError::OpenFileError { err })?;
+                Ok((
+                    file.display().to_string(),
+                    sums::parse_sums(std::io::BufReader::new(reader), args.encoding)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, failure::Error>>()?
+    };
+    // lines with a digest of another length belong to another algorithm
+    let output_size = new_hasher().output_size();
+    let mut entries = vec![];
+    let mut malformed = 0;
+    for (name, sums_file) in sums_files {
+        let before = entries.len();
+        malformed += sums_file.malformed;
+        for entry in sums_file.entries {
+            if entry.digest.len() == output_size {
+                entries.push(entry);
+            } else {
+                malformed += 1;
+            }
+        }
+        if entries.len() == before {
+            Err(ChecksumError::NoChecksumLines { file: name })?;
+        }
+    }
+
+    let (pbs, multi_bar_thread) = prepare_multi_bar(entries.len(), args.silent);
+    let check_outputs: Vec<_> = entries
+        .par_iter()
+        .zip(pbs)
+        .map(|(entry, pb)| {
+            let hasher = new_hasher();
+            match ProgressRead::from_file_path(&entry.path, pb, args.silent) {
+                Ok(progress_file) => Some(
+                    hasher
+                        .from_input(progress_file)
+                        .map(|hash| hash.as_ref() == entry.digest.as_slice()),
+                ),
+                // only absent files are missing, others could not be opened or read
+                Err(err) if is_not_found(&err) => None,
+                Err(err) => Some(Err(err)),
+            }
+        })
+        .collect();
+    multi_bar_thread.join().unwrap();
+
+    let (mut failed, mut missing) = (0, 0);
+    for (entry, result) in entries.iter().zip(check_outputs) {
+        match result {
+            Some(Ok(true)) => println!("{}: OK", entry.path.display()),
+            Some(Ok(false)) => {
+                failed += 1;
+                println!("{}: FAILED", entry.path.display());
+            }
+            Some(Err(err)) => {
+                failed += 1;
+                println!("{}: FAILED ({})", entry.path.display(), err);
+            }
+            None => {
+                missing += 1;
+                println!("{}: MISSING", entry.path.display());
+            }
+        }
+    }
+    if malformed > 0 {
+        eprintln!("WARNING: {} line(s) are improperly formatted", malformed);
+    }
+    if failed > 0 || missing > 0 {
+        Err(ChecksumError::CheckFailed { failed, missing })?;
+    }
+    Ok(())
+}
+
+fn is_not_found(err: &failure::Error) -> bool {
+    match err.downcast_ref::<ReadError>() {
+        Some(ReadError::OpenFileError { err }) | Some(ReadError::MetadataError { err }) => {
+            err.kind() == std::io::ErrorKind::NotFound
+        }
+        _ => false,
+    }
+}
+
+/// Result of hashing one file: the digest, file size and time taken
+struct FileHash {
+    result: Result<Vec<u8>, failure::Error>,
+    size: Option<u64>,
+    elapsed: Duration,
+}
+
+impl FileHash {
+    fn into_record(self, path: PathBuf, algorithm: &str) -> HashRecord {
+        HashRecord {
+            path: Some(path),
+            algorithm: algorithm.to_owned(),
+            result: self.result.map_err(|err| err.to_string()),
+            size: self.size,
+            elapsed: self.elapsed,
+        }
+    }
+}
+
+/// Hashes one file with every worker, see `sha256_tree::hash_file` and `blake::hash_file`
+type SplitHasher =
+    fn(&Path, ByteRange, Option<ProgressBar>, bool) -> Result<Vec<u8>, failure::Error>;
+
+/// How `hash_files` reads the files
+#[derive(Clone, Copy, Default)]
+struct HashOptions<'a> {
+    /// Part of each file to hash
+    range: ByteRange,
+    silent: bool,
+    /// Digests of files unchanged since they were cached are not computed again
+    cache: Option<&'a HashCache>,
+    /// Used instead of the hasher when there are fewer files than workers
+    split: Option<SplitHasher>,
+}
+
+/// The hasher splitting a single file among the workers, for algorithms that have one
+fn split_hasher(algorithm: &str) -> Option<SplitHasher> {
+    match algorithm {
+        "sha2-256-tree" => Some(sha256_tree::hash_file),
+        "blake3" => Some(blake::hash_file),
+        _ => None,
+    }
+}
+
+/// Hash the files in parallel, each with its own progress bar.
+/// With fewer files than workers and a split hasher, files are instead hashed
+/// one after another with the workers sharing each file
+fn hash_files<C: Checksum, F: Fn() -> C + Sync>(
+    files: &[PathBuf],
+    options: HashOptions,
+    new_hasher: &F,
+) -> Vec<FileHash> {
+    let HashOptions {
+        range,
+        silent,
+        cache,
+        split,
+    } = options;
+    let split = split.filter(|_| files.len() < rayon::current_num_threads());
+    let hash_file = |(file, pb): (&PathBuf, Option<ProgressBar>)| {
+        let start = Instant::now();
+        if is_stdin(file) {
+            if let Some(pb) = pb {
+                pb.finish_and_clear();
+            }
+            let result = stdin_range(range)
+                .map_err(failure::Error::from)
+                .and_then(|stdin| new_hasher().from_reader(stdin))
+                .map(|hash| hash.as_ref().to_vec());
+            return FileHash {
+                result,
+                size: None,
+                elapsed: start.elapsed(),
+            };
+        }
+        if let Some(digest) = cache.and_then(|cache| cache.get(file)) {
+            if let Some(pb) = pb {
+                pb.finish_and_clear();
+            }
+            return FileHash {
+                size: FileStamp::of(file).ok().map(|stamp| stamp.size),
+                result: Ok(digest),
+                elapsed: start.elapsed(),
+            };
+        }
+        let stamp = FileStamp::of(file).ok();
+        let mut size = None;
+        let result = match split {
+            Some(split) => {
+                size = stamp.and_then(|stamp| range.len_within(stamp.size));
+                split(file, range, pb, silent)
+            }
+            None => match ProgressRead::from_file_range(file, range, pb, silent) {
+                Ok(progress_file) => {
+                    size = progress_file.total_bytes();
+                    new_hasher()
+                        .from_input(progress_file)
+                        .map(|hash| hash.as_ref().to_vec())
+                }
+                Err(err) => Err(err),
+            },
+        };
+        if let (Some(cache), Some(stamp), Ok(digest)) = (cache, stamp, &result) {
+            cache.insert(file, stamp, digest);
+        }
+        FileHash {
+            result,
+            size,
+            elapsed: start.elapsed(),
+        }
+    };
+    let (pbs, multi_bar_thread) = prepare_multi_bar(files.len(), silent);
+    let hash_outputs: Vec<_> = if split.is_some() {
+        files.iter().zip(pbs).map(hash_file).collect()
+    } else {
+        files.par_iter().zip(pbs).map(hash_file).collect()
+    };
+    multi_bar_thread.join().unwrap();
+    hash_outputs
+}
+
+/// Every file below the directories given, or the current one, as (full path, path shown)
+fn walk_entries(args: &HashArgs) -> Result<Vec<(PathBuf, PathBuf)>, failure::Error> {
+    let roots = if args.filenames.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        args.filenames.clone()
+    };
+    // (full path, path shown in the manifest)
+    let mut entries = vec![];
+    for root in &roots {
+        if root.is_dir() {
+            for relative in walk::walk_files_filtered(root, &args.filter)? {
+                let full_path = root.join(&relative);
+                // paths stay relative to the root unless several roots are given
+                let shown = if roots.len() > 1 {
+                    full_path.clone()
+                } else {
+                    relative
+                };
+                entries.push((full_path, shown));
+            }
+        } else {
+            entries.push((root.clone(), root.clone()));
+        }
+    }
+    Ok(entries)
+}
+
+fn recursive_handler<C: Checksum, F: Fn() -> C + Sync>(
+    args: &HashArgs,
+    algorithm: &str,
+    options: HashOptions,
+    new_hasher: &F,
+) -> Result<(), failure::Error> {
+    let mut entries = walk_entries(args)?;
+    if args.stores.contains(&ChecksumStore::Sidecar) {
+        // sidecars written by an earlier run are not files to checksum
+        entries.retain(|(file, _)| !stored::is_sidecar(file, algorithm));
+    }
+    let files: Vec<_> = entries.iter().map(|(file, _)| file.clone()).collect();
+    let stamps = stamp_files(args, &files);
+    let hash_outputs = hash_files(&files, options, new_hasher);
+    let stored = store_checksums(args, algorithm, &files, &stamps, &hash_outputs);
+    let records: Vec<_> = hash_outputs
+        .into_iter()
+        .zip(entries)
+        .map(|(output, (_, shown))| output.into_record(shown, algorithm))
+        .collect();
+    let failed = records
+        .iter()
+        .filter(|record| record.result.is_err())
+        .count();
+    format::write_records(
+        args.format.unwrap_or(OutputFormat::Gnu),
+        args.encoding,
+        &records,
+        &mut std::io::stdout().lock(),
+        &mut std::io::stderr().lock(),
+    )?;
+    if failed > 0 {
+        Err(ChecksumError::HashFailed { count: failed })?;
+    }
+    stored
+}
+
+/// Stamps of the files whose checksums are to be stored, taken before hashing
+/// them. Only regular files get a stamp, and nothing is stored for the others
+fn stamp_files(args: &HashArgs, files: &[PathBuf]) -> Vec<Option<FileStamp>> {
+    files
+        .iter()
+        .map(|file| {
+            if args.stores.is_empty() || is_stdin(file) {
+                None
+            } else {
+                FileStamp::of_cacheable(file)
+            }
+        })
+        .collect()
+}
+
+/// Write the digests of the hashed files to every store of `args`. Nothing is
+/// stored for a file changed while it was hashed, its digest may mix old and new content
+fn store_checksums(
+    args: &HashArgs,
+    algorithm: &str,
+    files: &[PathBuf],
+    stamps: &[Option<FileStamp>],
+    hash_outputs: &[FileHash],
+) -> Result<(), failure::Error> {
+    let mut failed = 0;
+    for ((file, stamp), output) in files.iter().zip(stamps).zip(hash_outputs) {
+        if let (Some(stamp), Ok(digest)) = (stamp, &output.result) {
+            if FileStamp::of_cacheable(file).as_ref() != Some(stamp) {
+                eprintln!(
+                    "{}: changed while hashed, checksum not stored",
+                    file.display()
+                );
+                failed += 1;
+                continue;
+            }
+            for store in &args.stores {
+                if let Err(err) = store.write(file, algorithm, digest, stamp) {
+                    eprintln!("{}: can not store checksum: {}", file.display(), err);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        Err(ChecksumError::StoreFailed { count: failed })?;
+    }
+    Ok(())
+}
+
+/// Hash files again and compare them with the checksums stored for them. With
+/// sidecars, a sidecar among the files stands for the file it belongs to
+fn scrub_handler<C: Checksum, F: Fn() -> C + Sync>(
+    args: &HashArgs,
+    algorithm: &str,
+    new_hasher: &F,
+) -> Result<(), failure::Error> {
+    let entries = if args.recursive {
+        walk_entries(args)?
+    } else {
+        args.filenames
+            .iter()
+            .filter(|file| !is_stdin(file))
+            .map(|file| (file.clone(), file.clone()))
+            .collect()
+    };
+    let uses_sidecars = args.stores.contains(&ChecksumStore::Sidecar);
+    let mut seen = HashSet::new();
+    let mut files = vec![];
+    for (file, shown) in entries {
+        let (file, shown) = match stored::sidecar_target(&file, algorithm) {
+            Some(target) if uses_sidecars && stored::is_sidecar(&file, algorithm) => {
+                let shown = stored::sidecar_target(&shown, algorithm).unwrap_or(shown);
+                (target, shown)
+            }
+            _ => (file, shown),
+        };
+        if seen.insert(file.clone()) {
+            files.push((file, shown));
+        }
+    }
+
+    let mut summary = ScrubSummary::default();
+    // files found intact are only counted
+    let mut report = |shown: &Path, status: ScrubStatus, detail: Option<String>| {
+        summary.add(status);
+        match detail {
+            _ if status == ScrubStatus::Ok => {}
+            Some(detail) => println!("{}: {} ({})", shown.display(), status.label(), detail),
+            None => println!("{}: {}", shown.display(), status.label()),
+        }
+    };
+    let mut to_hash = vec![];
+    for (file, shown) in files {
+        if std::fs::symlink_metadata(&file).is_err() {
+            report(&shown, ScrubStatus::Missing, None);
+            continue;
+        }
+        let stamp = match FileStamp::of_cacheable(&file) {
+            Some(stamp) => stamp,
+            None => {
+                report(
+                    &shown,
+                    ScrubStatus::Unverified,
+                    Some("not a regular file".to_owned()),
+                );
+                continue;
+            }
+        };
+        let checksums = args
+            .stores
+            .iter()
+            .filter_map(|store| store.read(&file, algorithm).transpose())
+            .collect::<std::io::Result<Vec<_>>>();
+        match checksums {
+            Ok(checksums) if checksums.is_empty() => report(
+                &shown,
+                ScrubStatus::Unverified,
+                Some("no stored checksum".to_owned()),
+            ),
+            Ok(checksums) => to_hash.push((file, shown, stamp, checksums)),
+            Err(err) => report(&shown, ScrubStatus::Unverified, Some(err.to_string())),
+        }
+    }
+
+    let paths: Vec<_> = to_hash.iter().map(|(file, ..)| file.clone()).collect();
+    let options = HashOptions {
+        silent: args.silent,
+        split: split_hasher(algorithm),
+        ..HashOptions::default()
+    };
+    let hash_outputs = hash_files(&paths, options, new_hasher);
+    for ((_, shown, stamp, checksums), output) in to_hash.iter().zip(hash_outputs) {
+        match output.result {
+            Ok(digest) => report(shown, ScrubStatus::of(checksums, &digest, stamp), None),
+            Err(err) => report(shown, ScrubStatus::Unverified, Some(err.to_string())),
+        }
+    }
+    summary.write(&mut std::io::stdout().lock())?;
+    if summary.corrupted > 0 || summary.missing > 0 {
+        Err(ChecksumError::ScrubFailed {
+            corrupted: summary.corrupted,
+            missing: summary.missing,
+        })?;
+    }
+    Ok(())
+}
+
+fn tree_handler<C: Checksum, F: Fn() -> C + Sync>(
+    args: &HashArgs,
+    algorithm: &str,
+    new_hasher: &F,
+) -> Result<(), failure::Error> {
+    let roots = if args.filenames.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        args.filenames.clone()
+    };
+    let mut records = vec![];
+    for root in &roots {
+        let start = Instant::now();
+        let tree = tree::hash_tree(root, &args.filter, args.tree_modes, new_hasher, args.silent)?;
+        let elapsed = start.elapsed();
+        let nodes = if args.recursive {
+            tree.nodes()
+        } else {
+            vec![&tree]
+        };
+        for node in nodes {
+            let mut path = node.full_path(root).into_os_string();
+            if node.kind == NodeKind::Dir && !path.to_string_lossy().ends_with('/') {
+                path.push("/");
+            }
+            // nodes are hashed together, the time taken is the one of the whole tree
+            records.push(HashRecord {
+                path: Some(PathBuf::from(path)),
+                algorithm: algorithm.to_owned(),
+                result: Ok(node.digest.clone()),
+                size: None,
+                elapsed,
+            });
+        }
+    }
+    format::write_records(
+        args.format.unwrap_or(OutputFormat::Gnu),
+        args.encoding,
+        &records,
+        &mut std::io::stdout().lock(),
+        &mut std::io::stderr().lock(),
+    )?;
+    Ok(())
+}
+
+/// Name digests are cached under, along with the parameters of extendable-output
+/// functions since they change every digest
+fn cache_algorithm<C: Checksum, F: Fn() -> C>(
+    args: &HashArgs,
+    algorithm: &str,
+    new_hasher: &F,
+) -> String {
+    if !algorithm.contains("shake") {
+        return algorithm.to_owned();
+    }
+    let mut name = format!("{}/{}", algorithm, new_hasher().output_size());
+    if !args.function_name.is_empty() || !args.customization.is_empty() {
+        name.push_str(&format!(
+            "/{}/{}",
+            Encoding::Hex.encode(args.function_name.as_bytes()),
+            Encoding::Hex.encode(args.customization.as_bytes())
+        ));
+    }
+    name
+}
+
+fn run_hash_handler<C: Checksum, F: Fn() -> C + Sync>(
+    args: &HashArgs,
+    algorithm: &str,
+    new_hasher: F,
+) -> Result<(), failure::Error> {
     set_num_threads(args.parallels as usize);
+    set_io_mode(args.io);
+    set_symlink_policy(args.symlinks);
+    if args.check {
+        return check_handler(args, &new_hasher);
+    } else if args.compare {
+        return compare_blocks_handler(args);
+    } else if let Some(block_size) = args.block_size {
+        return blocks_handler(args, block_size, &new_hasher);
+    } else if let Some(checkpoint) = &args.checkpoint {
+        return checkpoint_handler(args, algorithm, checkpoint);
+    } else if args.scrub {
+        return scrub_handler(args, algorithm, &new_hasher);
+    }
+    let cache = args
+        .cache
+        .as_ref()
+        .map(|path| {
+            let cache_algorithm = cache_algorithm(args, algorithm, &new_hasher);
+            HashCache::open(path, &cache_algorithm, args.rehash)
+        })
+        .transpose()?;
+    let options = HashOptions {
+        range: args.range,
+        silent: args.silent,
+        cache: cache.as_ref(),
+        split: split_hasher(algorithm),
+    };
+    let result = if args.tree {
+        tree_handler(args, algorithm, &new_hasher)
+    } else if args.recursive {
+        recursive_handler(args, algorithm, options, &new_hasher)
+    } else if args.filenames.is_empty() {
+        hash_stdin(args, algorithm, new_hasher)
+    } else {
+        let stamps = stamp_files(args, &args.filenames);
+        let hash_outputs = hash_files(&args.filenames, options, &new_hasher);
+        let stored = store_checksums(args, algorithm, &args.filenames, &stamps, &hash_outputs);
+        let printed = if let Some(format) = args.format {
+            let records: Vec<_> = hash_outputs
+                .into_iter()
+                .zip(args.filenames.iter())
+                .map(|(output, file)| output.into_record(file.clone(), algorithm))
+                .collect();
+            format::write_records(
+                format,
+                args.encoding,
+                &records,
+                &mut std::io::stdout().lock(),
+                &mut std::io::stderr().lock(),
+            )
+            .map_err(failure::Error::from)
+        } else {
+            for (file, output) in args.filenames.iter().zip(hash_outputs) {
+                match output.result {
+                    Err(err) => println!("[{:?}] error: {}", file, err),
+                    Ok(hash) => println!("[{:?}] = {}", file, args.encoding.encode(&hash)),
+                }
+            }
+            Ok(())
+        };
+        printed.and(stored)
+    };
+    // digests computed before a failure are still worth keeping
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+    result
+}
+
+/// Print the whole-file digest and one digest per block of every file
+fn blocks_handler<C: Checksum, F: Fn() -> C + Sync>(
+    args: &HashArgs,
+    block_size: u64,
+    new_hasher: &F,
+) -> Result<(), failure::Error> {
+    if block_size == 0 {
+        Err(ArgumentError::InvalidSize {
+            value: "0".to_owned(),
+        })?;
+    }
     if args.filenames.is_empty() {
-        let hasher = D::new();
-        let result = hasher.from_reader(std::io::stdin().lock())?;
-        result
-            .as_slice()
+        let mut list =
+            Blockwise::new(block_size, new_hasher).from_reader(std::io::stdin().lock())?;
+        list.path = PathBuf::from("-");
+        list.write(args.encoding, &mut std::io::stdout().lock())?;
+        return Ok(());
+    }
+    let (pbs, multi_bar_thread) = prepare_multi_bar(args.filenames.len(), args.silent);
+    let outputs: Vec<_> = args
+        .filenames
+        .par_iter()
+        .zip(pbs)
+        .map(|(file, pb)| -> Result<BlockList, failure::Error> {
+            let progress_file = ProgressRead::from_file_path(file, pb, args.silent)?;
+            let mut list = Blockwise::new(block_size, new_hasher).from_input(progress_file)?;
+            list.path = file.clone();
+            Ok(list)
+        })
+        .collect();
+    multi_bar_thread.join().unwrap();
+
+    let mut failed = 0;
+    let mut out = std::io::stdout();
+    for (file, output) in args.filenames.iter().zip(outputs) {
+        match output {
+            Ok(list) => list.write(args.encoding, &mut out.lock())?,
+            Err(err) => {
+                eprintln!("{}: {}", file.display(), err);
+                failed += 1;
+            }
+        }
+    }
+    out.flush()?;
+    if failed > 0 {
+        Err(ChecksumError::HashFailed { count: failed })?;
+    }
+    Ok(())
+}
+
+/// Read a file of block lists
+fn read_block_lists(path: &Path, args: &HashArgs) -> Result<Vec<BlockList>, failure::Error> {
+    let reader = std::fs::File::open(path).map_err(|err| ReadError::OpenFileError { err })?;
+    let lists = blocks::parse_block_lists(std::io::BufReader::new(reader), args.encoding)?;
+    if lists.is_empty() {
+        Err(ChecksumError::NoChecksumLines {
+            file: path.display().to_string(),
+        })?;
+    }
+    Ok(lists)
+}
+
+/// Compare two block lists and print the byte ranges that differ.
+/// Lists of a single file are compared whatever the file names,
+/// otherwise files are matched by path
+fn compare_blocks_handler(args: &HashArgs) -> Result<(), failure::Error> {
+    let (old_path, new_path) = match args.filenames.as_slice() {
+        [old, new] => (old, new),
+        _ => Err(failure::format_err!(
+            "--compare takes exactly two block lists"
+        ))?,
+    };
+    let old_lists = read_block_lists(old_path, args)?;
+    let new_lists = read_block_lists(new_path, args)?;
+    let pairs: Vec<_> = if old_lists.len() == 1 && new_lists.len() == 1 {
+        vec![(&old_lists[0], Some(&new_lists[0]))]
+    } else {
+        old_lists
             .iter()
-            .for_each(|byte| print!("{:x}", byte));
-        println!("");
+            .map(|old| (old, new_lists.iter().find(|new| new.path == old.path)))
+            .collect()
+    };
+
+    let mut count = 0;
+    for (old, new) in pairs {
+        let new = match new {
+            Some(new) => new,
+            None => {
+                println!(
+                    "{}: missing from {}",
+                    old.path.display(),
+                    new_path.display()
+                );
+                count += 1;
+                continue;
+            }
+        };
+        let ranges = blocks::diff_ranges(old, new)?;
+        if ranges.is_empty() {
+            println!("{} {}: identical", old.path.display(), new.path.display());
+            continue;
+        }
+        println!(
+            "{} {}: {} range(s) differ",
+            old.path.display(),
+            new.path.display(),
+            ranges.len()
+        );
+        for (start, end) in &ranges {
+            println!("  bytes {}-{} ({} bytes)", start, end, end - start + 1);
+        }
+        count += ranges.len();
+    }
+    if count > 0 {
+        Err(ChecksumError::BlocksDiffer {
+            count,
+            old: old_path.display().to_string(),
+            new: new_path.display().to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Hash a single file with a hasher whose state can be saved, see `resume_hash`
+fn checkpoint_handler(
+    args: &HashArgs,
+    algorithm: &str,
+    checkpoint: &Path,
+) -> Result<(), failure::Error> {
+    match algorithm {
+        "sha2-224" => resume_hash(args, algorithm, checkpoint, Sha256State::sha224()),
+        "sha2-256" => resume_hash(args, algorithm, checkpoint, Sha256State::sha256()),
+        "sha2-384" => resume_hash(args, algorithm, checkpoint, Sha512State::sha384()),
+        "sha2-512" => resume_hash(args, algorithm, checkpoint, Sha512State::sha512()),
+        "crc32" => resume_hash(args, algorithm, checkpoint, crate::hashes::Crc32::default()),
+        "crc32c" => resume_hash(
+            args,
+            algorithm,
+            checkpoint,
+            crate::hashes::Crc32c::default(),
+        ),
+        "adler32" => resume_hash(
+            args,
+            algorithm,
+            checkpoint,
+            crate::hashes::Adler32::default(),
+        ),
+        _ => Err(ChecksumError::StateUnsupported {
+            name: algorithm.to_owned(),
+        })?,
+    }
+}
+
+/// Hash a single file, saving the hasher state to the checkpoint every
+/// `checkpoint_interval` bytes and when reading fails. With `--resume` an existing
+/// checkpoint of the same file is restored and reading continues from its offset
+fn resume_hash<R: Resumable>(
+    args: &HashArgs,
+    algorithm: &str,
+    checkpoint_path: &Path,
+    mut hasher: R,
+) -> Result<(), failure::Error> {
+    let file = match args.filenames.as_slice() {
+        [file] => file,
+        _ => Err(failure::format_err!("--checkpoint takes exactly one file"))?,
+    };
+    let mut checkpoint = Checkpoint {
+        algorithm: algorithm.to_owned(),
+        path: std::fs::canonicalize(file).map_err(|err| ReadError::OpenFileError { err })?,
+        stamp: FileStamp::of(file).map_err(|err| ReadError::MetadataError { err })?,
+        offset: 0,
+        state: vec![],
+    };
+    if args.resume {
+        let saved = Checkpoint::load(checkpoint_path)
+            .map_err(|err| failure::format_err!("{}: {}", checkpoint_path.display(), err))?;
+        if let Some(saved) = saved {
+            if saved.algorithm != checkpoint.algorithm
+                || saved.path != checkpoint.path
+                || saved.stamp != checkpoint.stamp
+            {
+                Err(ChecksumError::CheckpointMismatch {
+                    file: checkpoint_path.display().to_string(),
+                })?;
+            }
+            if !hasher.import_state(&saved.state) {
+                Err(ChecksumError::InvalidCheckpoint {
+                    file: checkpoint_path.display().to_string(),
+                })?;
+            }
+            checkpoint.offset = saved.offset;
+        }
+    }
+
+    let start = Instant::now();
+    let (mut pbs, multi_bar_thread) = prepare_multi_bar(1, args.silent);
+    let range = ByteRange {
+        offset: checkpoint.offset,
+        length: None,
+    };
+    // a failed read ends the loop with the error, other failures are returned
+    let read_error = ProgressRead::from_file_range(file, range, pbs.pop().unwrap(), args.silent)
+        .and_then(|mut input| {
+            let mut buffer = io::buffer();
+            let mut saved_at = checkpoint.offset;
+            loop {
+                let read_bytes = match input.read(&mut buffer) {
+                    Ok(0) => return Ok(None),
+                    Ok(read_bytes) => read_bytes,
+                    Err(err) => return Ok(Some(err)),
+                };
+                hasher.update(&buffer[..read_bytes]);
+                checkpoint.offset += read_bytes as u64;
+                if checkpoint.offset - saved_at >= args.checkpoint_interval {
+                    checkpoint.state = hasher.export_state();
+                    checkpoint.save(checkpoint_path)?;
+                    saved_at = checkpoint.offset;
+                }
+            }
+        });
+    multi_bar_thread.join().unwrap();
+    if let Some(err) = read_error? {
+        checkpoint.state = hasher.export_state();
+        checkpoint.save(checkpoint_path)?;
+        Err(ChecksumError::Interrupted {
+            offset: checkpoint.offset,
+            err,
+            checkpoint: checkpoint_path.display().to_string(),
+        })?;
+    }
+
+    let digest = hasher.finish().as_ref().to_vec();
+    match std::fs::remove_file(checkpoint_path) {
+        Err(ref err) if err.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!(
+                "can not remove checkpoint {}: {}",
+                checkpoint_path.display(),
+                err
+            )
+        }
+        _ => {}
+    }
+    if let Some(format) = args.format {
+        let record = HashRecord {
+            path: Some(file.clone()),
+            algorithm: algorithm.to_owned(),
+            result: Ok(digest),
+            size: Some(checkpoint.stamp.size),
+            elapsed: start.elapsed(),
+        };
+        format::write_records(
+            format,
+            args.encoding,
+            &[record],
+            &mut std::io::stdout().lock(),
+            &mut std::io::stderr().lock(),
+        )?;
+    } else {
+        println!("[{:?}] = {}", file, args.encoding.encode(&digest));
+    }
+    Ok(())
+}
+
+/// Hash the standard input, printed bare unless an output format is given
+fn hash_stdin<C: Checksum, F: Fn() -> C>(
+    args: &HashArgs,
+    algorithm: &str,
+    new_hasher: F,
+) -> Result<(), failure::Error> {
+    let hasher = new_hasher();
+    let start = Instant::now();
+    let result = hasher.from_reader(stdin_range(args.range)?)?;
+    if let Some(format) = args.format {
+        let record = HashRecord {
+            path: None,
+            algorithm: algorithm.to_owned(),
+            result: Ok(result.as_ref().to_vec()),
+            size: None,
+            elapsed: start.elapsed(),
+        };
+        format::write_records(
+            format,
+            args.encoding,
+            &[record],
+            &mut std::io::stdout().lock(),
+            &mut std::io::stderr().lock(),
+        )?;
+    } else {
+        println!("{}", args.encoding.encode(result.as_ref()));
+    }
+    Ok(())
+}
+
+/// Handler of checksums that can not be keyed
+fn hash_handler<C: Checksum + Default>(
+    matches: &ArgMatches,
+    algorithm: &str,
+) -> Result<(), failure::Error> {
+    let args = HashArgs::try_from(matches)?;
+    if args.hmac_key.is_some() {
+        Err(ChecksumError::HmacUnsupported {
+            name: algorithm.to_owned(),
+        })?;
+    }
+    run_hash_handler(&args, algorithm, C::default)
+}
+
+/// Handler of SHAKE and cSHAKE, `new_xof` creates a state with the output length
+fn xof_handler<K, F>(
+    matches: &ArgMatches,
+    algorithm: &str,
+    default_length: usize,
+    new_xof: F,
+) -> Result<(), failure::Error>
+where
+    K: tiny_keccak::Hasher + Send,
+    F: Fn(usize, &HashArgs) -> Xof<K> + Sync,
+{
+    let args = HashArgs::try_from(matches)?;
+    if args.hmac_key.is_some() {
+        Err(ChecksumError::HmacUnsupported {
+            name: algorithm.to_owned(),
+        })?;
+    }
+    let length = args.output_length.unwrap_or(default_length);
+    run_hash_handler(&args, algorithm, || new_xof(length, &args))
+}
+
+/// Handler of digests, computed as HMAC when a key is given
+fn digest_handler<D>(matches: &ArgMatches, algorithm: &str) -> Result<(), failure::Error>
+where
+    D: Input + BlockInput + FixedOutput + Reset + Default + Clone + Send,
+    D::BlockSize: ArrayLength<u8>,
+{
+    let args = HashArgs::try_from(matches)?;
+    match &args.hmac_key {
+        Some(key) => run_hash_handler(&args, &format!("hmac-{}", algorithm), || {
+            HmacChecksum::<D>::new(key)
+        }),
+        None => run_hash_handler(&args, algorithm, D::default),
+    }
+}
+
+pub fn multi_hash_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    let args = MultiHashArgs::try_from(matches)?;
+    let key = args.hmac_key.as_deref();
+    // check algorithm names before reading anything
+    Hashes::new(&args.algorithms, key)?;
+    set_num_threads(args.parallels as usize);
+    set_io_mode(args.io);
+    set_symlink_policy(args.symlinks);
+    if args.filenames.is_empty() {
+        let hashes = Hashes::new(&args.algorithms, key)?;
+        let start = Instant::now();
+        let digests = hashes.from_reader(std::io::stdin().lock())?;
+        if let Some(format) = args.format {
+            let records: Vec<_> = digests
+                .into_iter()
+                .map(|(name, hash)| HashRecord {
+                    path: None,
+                    algorithm: name,
+                    result: Ok(hash),
+                    size: None,
+                    elapsed: start.elapsed(),
+                })
+                .collect();
+            format::write_records(
+                format,
+                args.encoding,
+                &records,
+                &mut std::io::stdout().lock(),
+                &mut std::io::stderr().lock(),
+            )?;
+        } else {
+            for (name, hash) in digests {
+                println!("{} = {}", name, args.encoding.encode(&hash));
+            }
+        }
     } else {
         let (pbs, multi_bar_thread) = prepare_multi_bar(args.filenames.len(), args.silent);
 
@@ -31,25 +1005,65 @@ fn hash_handler<D: Digest>(matches: &ArgMatches) -> Result<(), failure::Error> {
             .par_iter()
             .zip(pbs)
             .map(|(file, pb)| {
-                let hasher = D::new();
-                (
-                    file,
-                    match ProgressRead::from_file_path(file, pb, args.silent) {
-                        Ok(progress_file) => hasher.from_reader(progress_file),
-                        Err(err) => Err(err),
-                    },
-                )
+                let start = Instant::now();
+                let mut size = None;
+                let result = Hashes::new(&args.algorithms, key).and_then(|hashes| {
+                    if is_stdin(file) {
+                        if let Some(pb) = pb {
+                            pb.finish_and_clear();
+                        }
+                        return hashes.from_reader(std::io::stdin().lock());
+                    }
+                    let progress_file = ProgressRead::from_file_path(file, pb, args.silent)?;
+                    size = progress_file.total_bytes();
+                    hashes.from_input(progress_file)
+                });
+                (file, result, size, start.elapsed())
             })
             .collect();
 
         multi_bar_thread.join().unwrap();
-        for (file, result) in hash_outputs {
+        if let Some(format) = args.format {
+            let mut records = vec![];
+            for (file, result, size, elapsed) in hash_outputs {
+                match result {
+                    Ok(digests) => {
+                        records.extend(digests.into_iter().map(|(name, hash)| HashRecord {
+                            path: Some(file.clone()),
+                            algorithm: name,
+                            result: Ok(hash),
+                            size,
+                            elapsed,
+                        }))
+                    }
+                    Err(err) => records.extend(args.algorithms.iter().map(|name| HashRecord {
+                        path: Some(file.clone()),
+                        algorithm: match key {
+                            Some(_) => format!("hmac-{}", name),
+                            None => name.clone(),
+                        },
+                        result: Err(err.to_string()),
+                        size,
+                        elapsed,
+                    })),
+                }
+            }
+            format::write_records(
+                format,
+                args.encoding,
+                &records,
+                &mut std::io::stdout().lock(),
+                &mut std::io::stderr().lock(),
+            )?;
+            return Ok(());
+        }
+        for (file, result, _, _) in hash_outputs {
             match result {
                 Err(err) => println!("[{:?}] error: {}", file, err),
-                Ok(hash) => {
-                    print!("[{:?}] = ", file);
-                    hash.as_slice().iter().for_each(|byte| print!("{:x}", byte));
-                    println!("");
+                Ok(digests) => {
+                    for (name, hash) in digests {
+                        println!("[{:?}] {} = {}", file, name, args.encoding.encode(&hash));
+                    }
                 }
             }
         }
@@ -58,42 +1072,726 @@ fn hash_handler<D: Digest>(matches: &ArgMatches) -> Result<(), failure::Error> {
 }
 
 pub fn sha1_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha1::Sha1>(matches)
+    digest_handler::<sha1::Sha1>(matches, "sha1")
 }
 pub fn sha2_256_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha2::Sha256>(matches)
+    digest_handler::<sha2::Sha256>(matches, "sha2-256")
 }
 pub fn sha2_224_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha2::Sha224>(matches)
+    digest_handler::<sha2::Sha224>(matches, "sha2-224")
 }
 pub fn sha2_384_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha2::Sha384>(matches)
+    digest_handler::<sha2::Sha384>(matches, "sha2-384")
 }
 pub fn sha2_512_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha2::Sha512>(matches)
+    digest_handler::<sha2::Sha512>(matches, "sha2-512")
 }
 pub fn sha3_256_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha3::Sha3_256>(matches)
+    digest_handler::<sha3::Sha3_256>(matches, "sha3-256")
 }
 pub fn sha3_224_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha3::Sha3_224>(matches)
+    digest_handler::<sha3::Sha3_224>(matches, "sha3-224")
 }
 pub fn sha3_384_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha3::Sha3_384>(matches)
+    digest_handler::<sha3::Sha3_384>(matches, "sha3-384")
 }
 pub fn sha3_512_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<sha3::Sha3_512>(matches)
+    digest_handler::<sha3::Sha3_512>(matches, "sha3-512")
+}
+pub fn shake128_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    xof_handler(matches, "shake128", SHAKE128_DEFAULT_LENGTH, |length, _| {
+        Xof::shake128(length)
+    })
+}
+pub fn shake256_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    xof_handler(matches, "shake256", SHAKE256_DEFAULT_LENGTH, |length, _| {
+        Xof::shake256(length)
+    })
+}
+pub fn cshake128_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    xof_handler(
+        matches,
+        "cshake128",
+        SHAKE128_DEFAULT_LENGTH,
+        |length, args| {
+            Xof::cshake128(
+                length,
+                args.function_name.as_bytes(),
+                args.customization.as_bytes(),
+            )
+        },
+    )
+}
+pub fn cshake256_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    xof_handler(
+        matches,
+        "cshake256",
+        SHAKE256_DEFAULT_LENGTH,
+        |length, args| {
+            Xof::cshake256(
+                length,
+                args.function_name.as_bytes(),
+                args.customization.as_bytes(),
+            )
+        },
+    )
+}
+pub fn sha2_256_tree_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    hash_handler::<Sha256Tree>(matches, "sha2-256-tree")
 }
 pub fn md5_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
-    hash_handler::<md5::Md5>(matches)
+    digest_handler::<md5::Md5>(matches, "md5")
+}
+pub fn blake2b_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    digest_handler::<blake2::Blake2b>(matches, "blake2b")
+}
+pub fn blake2s_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    digest_handler::<blake2::Blake2s>(matches, "blake2s")
+}
+pub fn blake3_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    digest_handler::<crate::hashes::Blake3>(matches, "blake3")
+}
+pub fn crc32_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    hash_handler::<crate::hashes::Crc32>(matches, "crc32")
+}
+pub fn crc32c_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    hash_handler::<crate::hashes::Crc32c>(matches, "crc32c")
+}
+pub fn crc64_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    hash_handler::<crate::hashes::Crc64>(matches, "crc64")
+}
+pub fn xxh3_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    hash_handler::<crate::hashes::Xxh3>(matches, "xxh3")
+}
+pub fn xxh128_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    hash_handler::<crate::hashes::Xxh128>(matches, "xxh128")
+}
+pub fn adler32_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    hash_handler::<crate::hashes::Adler32>(matches, "adler32")
 }
 
-use generic_array::typenum::Unsigned;
-fn cipher_handler<C: NewCipher + StreamCipher>(
-    matches: &ArgMatches,
+/// Digests listed in a manifest, or of every file below a directory.
+/// Manifest lines whose digest is not `output_size` bytes long are skipped
+fn audit_entries(
+    path: &Path,
+    args: &AuditArgs,
+    output_size: Option<usize>,
+) -> Result<Vec<(PathBuf, Vec<u8>)>, failure::Error> {
+    if path.is_dir() {
+        let relatives = walk::walk_files(path)?;
+        let files: Vec<_> = relatives
+            .iter()
+            .map(|relative| path.join(relative))
+            .collect();
+        let new_hasher = || registry::new_hasher(&args.algorithm).unwrap();
+        let options = HashOptions {
+            silent: args.silent,
+            split: split_hasher(&args.algorithm),
+            ..HashOptions::default()
+        };
+        let mut entries = vec![];
+        let mut failed = 0;
+        for ((relative, file), output) in
+            relatives
+                .into_iter()
+                .zip(&files)
+                .zip(hash_files(&files, options, &new_hasher))
+        {
+            match output.result {
+                Ok(digest) => entries.push((relative, digest)),
+                Err(err) => {
+                    eprintln!("{}: {}", file.display(), err);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            Err(ChecksumError::HashFailed { count: failed })?;
+        }
+        Ok(entries)
+    } else {
+        let reader = std::fs::File::open(path).map_err(|err| ReadError::OpenFileError { err })?;
+        let sums_file = sums::parse_sums(std::io::BufReader::new(reader), args.encoding)?;
+        let mut malformed = sums_file.malformed;
+        let mut entries = vec![];
+        for entry in sums_file.entries {
+            match output_size {
+                Some(size) if entry.digest.len() != size => malformed += 1,
+                _ => entries.push((entry.path, entry.digest)),
+            }
+        }
+        if entries.is_empty() {
+            Err(ChecksumError::NoChecksumLines {
+                file: path.display().to_string(),
+            })?;
+        }
+        if malformed > 0 {
+            eprintln!(
+                "WARNING: {} line(s) in {} are improperly formatted",
+                malformed,
+                path.display()
+            );
+        }
+        Ok(entries)
+    }
+}
+
+pub fn audit_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    let args = AuditArgs::try_from(matches)?;
+    let hasher =
+        registry::new_hasher(&args.algorithm).ok_or_else(|| ChecksumError::UnknownAlgorithm {
+            name: args.algorithm.clone(),
+            supported: ALGORITHMS.join(", "),
+        })?;
+    set_num_threads(args.parallels as usize);
+    set_io_mode(args.io);
+    // a manifest compared with a directory has to use the same algorithm
+    let output_size = if args.old.is_dir() || args.new.is_dir() {
+        Some(hasher.digest_size())
+    } else {
+        None
+    };
+    let old = audit_entries(&args.old, &args, output_size)?;
+    let new = audit_entries(&args.new, &args, output_size)?;
+    let changes = audit::audit(&old, &new);
+
+    let mut out = std::io::stdout();
+    if args.json {
+        audit::write_json(&changes, &mut out.lock())?;
+    } else {
+        audit::write_report(&changes, args.verbose, &mut out.lock())?;
+    }
+    out.flush()?;
+    let count = changes
+        .iter()
+        .filter(|change| !matches!(change, audit::Change::Unchanged(_)))
+        .count();
+    if count > 0 {
+        Err(ChecksumError::AuditFailed {
+            count,
+            old: args.old.display().to_string(),
+            new: args.new.display().to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Ask on the terminal whether to go on, anything but yes is taken as no
+fn confirm(question: &str) -> Result<bool, failure::Error> {
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Hard link or delete the duplicates of every set, deletions being confirmed
+/// set by set unless `yes`. Duplicates changed since `stamps` were taken or
+/// differing from the first file byte for byte are skipped
+fn dupes_action(
+    sets: &[DuplicateSet],
+    stamps: &HashMap<PathBuf, FileStamp>,
+    action: DupesAction,
+    yes: bool,
 ) -> Result<(), failure::Error> {
+    let mut failed = 0;
+    for set in sets {
+        let (original, duplicates) = set.files.split_first().unwrap();
+        if action == DupesAction::Delete && !yes {
+            let question = format!(
+                "delete {} duplicate(s) of {}?",
+                duplicates.len(),
+                original.display()
+            );
+            if !confirm(&question)? {
+                continue;
+            }
+        }
+        for duplicate in duplicates {
+            let unchanged = match stamps.get(duplicate) {
+                Some(stamp) => dupes::still_duplicate(original, duplicate, stamp),
+                None => Ok(false),
+            };
+            match unchanged {
+                Ok(true) => {}
+                Ok(false) => {
+                    eprintln!(
+                        "{}: skipped, changed since it was hashed or not the same as {}",
+                        duplicate.display(),
+                        original.display()
+                    );
+                    failed += 1;
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("{}: {}", duplicate.display(), err);
+                    failed += 1;
+                    continue;
+                }
+            }
+            let result = match action {
+                DupesAction::Hardlink => dupes::hardlink(original, duplicate),
+                DupesAction::Delete => std::fs::remove_file(duplicate),
+            };
+            if let Err(err) = result {
+                eprintln!("{}: {}", duplicate.display(), err);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        let action = match action {
+            DupesAction::Hardlink => "linked",
+            DupesAction::Delete => "deleted",
+        };
+        Err(ChecksumError::DupesActionFailed {
+            count: failed,
+            action: action.to_owned(),
+        })?;
+    }
+    Ok(())
+}
+
+pub fn dupes_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    let args = DupesArgs::try_from(matches)?;
+    if registry::new_hasher(&args.algorithm).is_none() {
+        Err(ChecksumError::UnknownAlgorithm {
+            name: args.algorithm.clone(),
+            supported: ALGORITHMS.join(", "),
+        })?;
+    }
+    let new_hasher = || registry::new_hasher(&args.algorithm).unwrap();
+    set_num_threads(args.parallels as usize);
+    set_io_mode(args.io);
+
+    let mut files = vec![];
+    for path in &args.paths {
+        if path.is_dir() {
+            let relatives = walk::walk_files_filtered(path, &args.filter)?;
+            files.extend(relatives.into_iter().map(|file| path.join(file)));
+        } else {
+            files.push(path.clone());
+        }
+    }
+    let mut failed = 0;
+    let (groups, errors) = dupes::group_by_size(files);
+    for (file, err) in errors {
+        eprintln!("{}: {}", file.display(), err);
+        failed += 1;
+    }
+    // candidates as they are before hashing, the actions leave alone files changed since
+    let stamps: HashMap<PathBuf, FileStamp> = groups
+        .iter()
+        .flat_map(|(_, files)| files)
+        .filter_map(|file| FileStamp::of(file).ok().map(|stamp| (file.clone(), stamp)))
+        .collect();
+
+    // files of the same size are told apart by their first and last blocks
+    let candidates: Vec<_> = groups
+        .into_iter()
+        .flat_map(|(size, files)| files.into_iter().map(move |file| (size, file)))
+        .collect();
+    let partials: Vec<_> = candidates
+        .par_iter()
+        .map(|(size, file)| dupes::partial_digest(file, *size, new_hasher()))
+        .collect();
+    let mut by_size: BTreeMap<u64, Vec<(PathBuf, Vec<u8>)>> = BTreeMap::new();
+    for ((size, file), partial) in candidates.into_iter().zip(partials) {
+        match partial {
+            Ok(digest) => by_size.entry(size).or_default().push((file, digest)),
+            Err(err) => {
+                eprintln!("{}: {}", file.display(), err);
+                failed += 1;
+            }
+        }
+    }
+    let mut sets = vec![];
+    let mut to_hash = vec![];
+    for (size, entries) in by_size {
+        for (digest, files) in dupes::group_by_digest(entries) {
+            if dupes::is_whole(size) {
+                sets.push(DuplicateSet {
+                    size,
+                    digest,
+                    files,
+                });
+            } else {
+                to_hash.push((size, files));
+            }
+        }
+    }
+
+    // the remaining candidates are hashed in full
+    let files: Vec<_> = to_hash
+        .iter()
+        .flat_map(|(_, files)| files.clone())
+        .collect();
+    let options = HashOptions {
+        silent: args.silent,
+        split: split_hasher(&args.algorithm),
+        ..HashOptions::default()
+    };
+    let mut hash_outputs = hash_files(&files, options, &new_hasher).into_iter();
+    for (size, files) in to_hash {
+        let mut entries = vec![];
+        for (file, output) in files.into_iter().zip(hash_outputs.by_ref()) {
+            match output.result {
+                Ok(digest) => entries.push((file, digest)),
+                Err(err) => {
+                    eprintln!("{}: {}", file.display(), err);
+                    failed += 1;
+                }
+            }
+        }
+        for (digest, files) in dupes::group_by_digest(entries) {
+            sets.push(DuplicateSet {
+                size,
+                digest,
+                files,
+            });
+        }
+    }
+    sets.sort_by(|a, b| {
+        b.wasted()
+            .cmp(&a.wasted())
+            .then_with(|| a.files[0].cmp(&b.files[0]))
+    });
+
+    let mut out = std::io::stdout();
+    if args.json {
+        dupes::write_json(&sets, args.encoding, &mut out.lock())?;
+    } else {
+        dupes::write_report(&sets, args.encoding, &mut out.lock())?;
+    }
+    out.flush()?;
+    if let Some(action) = args.action {
+        dupes_action(&sets, &stamps, action, args.yes)?;
+    }
+    if failed > 0 {
+        Err(ChecksumError::HashFailed { count: failed })?;
+    }
+    Ok(())
+}
+
+/// Hash every file below the root of `base` and record it with the same
+/// attributes and algorithms. The database file is left out when it is in the tree
+fn scan_database(
+    base: &Database,
+    database: &Path,
+    silent: bool,
+) -> Result<Database, failure::Error> {
+    let mut current = Database::new(
+        base.root.clone(),
+        base.attributes.clone(),
+        base.algorithms.clone(),
+    );
+    let database = std::fs::canonicalize(database).ok();
+    let mut relatives = walk::walk_files(&base.root)?;
+    relatives.retain(|file| {
+        database.is_none() || std::fs::canonicalize(base.root.join(file)).ok() != database
+    });
+    let files: Vec<_> = relatives.iter().map(|file| base.root.join(file)).collect();
+    // attributes are taken before the content, a file written meanwhile shows as changed
+    let metas: Vec<_> = files.iter().map(std::fs::metadata).collect();
+    let new_hasher = || Concatenated(Hashes::new(&base.algorithms, None).unwrap());
+    let sizes = new_hasher().0.output_sizes();
+    let options = HashOptions {
+        silent,
+        ..HashOptions::default()
+    };
+    let hash_outputs = hash_files(&files, options, &new_hasher);
+    let mut failed = 0;
+    for ((relative, meta), output) in relatives.into_iter().zip(metas).zip(hash_outputs) {
+        match (meta, output.result) {
+            (Ok(meta), Ok(digest)) => {
+                let record = current.record(&meta, multi::split_digests(&sizes, &digest));
+                current.records.insert(relative, record);
+            }
+            (Err(err), _) => {
+                eprintln!("{}: {}", base.root.join(&relative).display(), err);
+                failed += 1;
+            }
+            (_, Err(err)) => {
+                eprintln!("{}: {}", base.root.join(&relative).display(), err);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(ChecksumError::HashFailed { count: failed })?;
+    }
+    Ok(current)
+}
+
+pub fn db_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    let args = DbArgs::try_from(matches)?;
+    let key = args.hmac_key.as_deref();
+    let base = match args.command {
+        DbCommand::Init => {
+            if args.database.exists() {
+                Err(ChecksumError::DatabaseExists {
+                    file: args.database.display().to_string(),
+                })?;
+            }
+            // the root is recorded absolute so the database can be checked from anywhere
+            let root = args.root.clone().unwrap_or_else(|| PathBuf::from("."));
+            let root = std::fs::canonicalize(&root).map_err(|err| ReadError::ReadDirError {
+                err,
+                dir: root.display().to_string(),
+            })?;
+            Database::new(root, args.attributes.clone(), args.algorithms.clone())
+        }
+        DbCommand::Check | DbCommand::Update => {
+            let mut base = Database::load(&args.database, key)?;
+            // the tree may have moved since it was recorded
+            if let Some(root) = &args.root {
+                base.root = root.clone();
+            }
+            base
+        }
+    };
+    // check algorithm names before reading anything
+    Hashes::new(&base.algorithms, None)?;
+    set_num_threads(args.parallels as usize);
+    set_io_mode(args.io);
+
+    let current = scan_database(&base, &args.database, args.silent)?;
+    if args.command == DbCommand::Init {
+        current.save(&args.database, key)?;
+        println!("{} file(s) recorded", current.records.len());
+        return Ok(());
+    }
+    let drift = base.drift(&current);
+    for (path, change) in &drift {
+        match change {
+            Drift::Changed(names) => {
+                println!(
+                    "{}: {} ({})",
+                    change.label(),
+                    path.display(),
+                    names.join(", ")
+                )
+            }
+            _ => println!("{}: {}", change.label(), path.display()),
+        }
+    }
+    let count = |label| {
+        drift
+            .iter()
+            .filter(|(_, change)| change.label() == label)
+            .count()
+    };
+    println!(
+        "{} file(s) checked: {} changed, {} added, {} removed",
+        current.records.len(),
+        count("changed"),
+        count("added"),
+        count("removed")
+    );
+    if args.command == DbCommand::Update {
+        current.save(&args.database, key)?;
+        println!("{} updated", args.database.display());
+    } else if !drift.is_empty() {
+        Err(ChecksumError::DatabaseDrift {
+            count: drift.len(),
+            file: args.database.display().to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Digests of files of the tree at `root` by path and algorithm, every algorithm
+/// computed in a single read of each file
+fn hash_tree_files(
+    root: &Path,
+    paths: &[PathBuf],
+    algorithms: &[String],
+    silent: bool,
+) -> Result<HashMap<PathBuf, HashMap<String, Vec<u8>>>, failure::Error> {
+    if algorithms.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let files: Vec<_> = paths.iter().map(|path| root.join(path)).collect();
+    let new_hasher = || Concatenated(Hashes::new(algorithms, None).unwrap());
+    let sizes = new_hasher().0.output_sizes();
+    let options = HashOptions {
+        silent,
+        ..HashOptions::default()
+    };
+    let hash_outputs = hash_files(&files, options, &new_hasher);
+    let mut digests = HashMap::new();
+    let mut failed = 0;
+    for ((path, file), output) in paths.iter().zip(&files).zip(hash_outputs) {
+        match output.result {
+            Ok(digest) => {
+                let by_algorithm = algorithms
+                    .iter()
+                    .cloned()
+                    .zip(multi::split_digests(&sizes, &digest))
+                    .collect();
+                digests.insert(path.clone(), by_algorithm);
+            }
+            Err(err) => {
+                eprintln!("{}: {}", file.display(), err);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(ChecksumError::HashFailed { count: failed })?;
+    }
+    Ok(digests)
+}
+
+/// Report the entries of the specification that differ from the tree, then the
+/// entries of the tree the specification does not describe
+fn verify_mtree(args: &MtreeArgs, spec: &Path, nodes: &[Node]) -> Result<(), failure::Error> {
+    let reader = std::fs::File::open(spec).map_err(|err| ReadError::OpenFileError { err })?;
+    let entries = mtree::parse_spec(std::io::BufReader::new(reader), &spec.display().to_string())?;
+    let by_path: HashMap<&Path, &Node> = nodes
+        .iter()
+        .map(|node| (node.path.as_path(), node))
+        .collect();
+
+    // files are hashed with every algorithm the specification uses
+    let mut algorithms: Vec<String> = vec![];
+    for entry in &entries {
+        for algorithm in entry
+            .keywords
+            .keys()
+            .filter_map(|keyword| mtree::digest_algorithm(keyword))
+        {
+            if !algorithms.iter().any(|known| known == algorithm) {
+                algorithms.push(algorithm.to_owned());
+            }
+        }
+    }
+    let to_hash: Vec<_> = entries
+        .iter()
+        .filter(|entry| {
+            entry
+                .keywords
+                .keys()
+                .any(|keyword| mtree::digest_algorithm(keyword).is_some())
+        })
+        .filter_map(|entry| by_path.get(entry.path.as_path()))
+        .filter(|node| node.is_file())
+        .map(|node| node.path.clone())
+        .collect();
+    let digests = hash_tree_files(&args.root, &to_hash, &algorithms, args.silent)?;
+
+    let no_digests = HashMap::new();
+    let (mut changed, mut missing, mut extra) = (0, 0, 0);
+    for entry in &entries {
+        match by_path.get(entry.path.as_path()) {
+            None if entry.is_optional() => {}
+            None => {
+                println!("missing: {}", mtree::spec_path(&entry.path));
+                missing += 1;
+            }
+            Some(node) => {
+                let node_digests = digests.get(&entry.path).unwrap_or(&no_digests);
+                let differences = mtree::differences(&args.root, entry, node, node_digests);
+                if !differences.is_empty() {
+                    let path = mtree::spec_path(&entry.path);
+                    println!("changed: {} ({})", path, differences.join(", "));
+                    changed += 1;
+                }
+            }
+        }
+    }
+    let described: HashSet<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+    let ignored: Vec<&Path> = entries
+        .iter()
+        .filter(|entry| entry.ignores_children())
+        .map(|entry| entry.path.as_path())
+        .collect();
+    for node in nodes {
+        let is_ignored = ignored
+            .iter()
+            .any(|dir| node.path.starts_with(dir) && node.path != *dir);
+        if !described.contains(node.path.as_path()) && !is_ignored {
+            println!("extra: {}", mtree::spec_path(&node.path));
+            extra += 1;
+        }
+    }
+    println!(
+        "{} entries checked: {} changed, {} missing, {} extra",
+        entries.len(),
+        changed,
+        missing,
+        extra
+    );
+    if changed + missing + extra > 0 {
+        Err(ChecksumError::SpecMismatch {
+            count: changed + missing + extra,
+            file: spec.display().to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+pub fn mtree_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    let args = MtreeArgs::try_from(matches)?;
+    set_num_threads(args.parallels as usize);
+    set_io_mode(args.io);
+    let nodes = mtree::scan(&args.root).map_err(|err| ReadError::ReadDirError {
+        err,
+        dir: args.root.display().to_string(),
+    })?;
+    if let Some(spec) = &args.spec {
+        return verify_mtree(&args, spec, &nodes);
+    }
+    let algorithms: Vec<String> = args
+        .keywords
+        .iter()
+        .filter_map(|keyword| mtree::digest_algorithm(keyword))
+        .map(str::to_owned)
+        .collect();
+    let files: Vec<_> = nodes
+        .iter()
+        .filter(|node| node.is_file())
+        .map(|node| node.path.clone())
+        .collect();
+    let digests = hash_tree_files(&args.root, &files, &algorithms, args.silent)?;
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    mtree::write_spec(&args.root, &nodes, &args.keywords, &digests, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn git_hash_handler(matches: &ArgMatches) -> Result<(), failure::Error> {
+    let args = GitHashArgs::try_from(matches)?;
+    set_num_threads(args.parallels as usize);
+    let mut failed = 0;
+    for path in &args.paths {
+        let id = if is_stdin(path) {
+            args.format
+                .blob_id_from_reader(std::io::stdin().lock())
+                .map_err(failure::Error::from)
+        } else if path.is_dir() {
+            args.format.tree_id(path)
+        } else {
+            args.format.blob_id(path)
+        };
+        match id {
+            Ok(id) => println!("{}", Encoding::Hex.encode(&id)),
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(ChecksumError::HashFailed { count: failed })?;
+    }
+    Ok(())
+}
+
+fn cipher_handler<C: NewCipher + StreamCipher>(matches: &ArgMatches) -> Result<(), failure::Error> {
     let args = CipherArgs::try_from(matches)?;
     set_num_threads(args.parallels as usize);
+    set_io_mode(args.io);
     let key_len = C::KeySize::to_usize();
     let iv_len = C::NonceSize::to_usize();
     let mut key_iv = vec![0; key_len + iv_len];
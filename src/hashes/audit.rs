@@ -0,0 +1,163 @@
+//! Comparison of two sets of file digests, in the style of hashdeep's audit mode.
+use super::format::json_string;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    /// Same path, same digest
+    Unchanged(PathBuf),
+    /// Same path, different digest
+    Modified(PathBuf),
+    /// Only in the new set
+    Added(PathBuf),
+    /// Only in the old set
+    Removed(PathBuf),
+    /// Same digest found under another path
+    Moved { from: PathBuf, to: PathBuf },
+}
+
+impl Change {
+    /// Label of the change in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::Unchanged(_) => "unchanged",
+            Change::Modified(_) => "modified",
+            Change::Added(_) => "added",
+            Change::Removed(_) => "removed",
+            Change::Moved { .. } => "moved",
+        }
+    }
+
+    /// The path the change is sorted by, the new path for moves
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Unchanged(path)
+            | Change::Modified(path)
+            | Change::Added(path)
+            | Change::Removed(path) => path,
+            Change::Moved { to, .. } => to,
+        }
+    }
+}
+
+/// Drop `.` components so that `./a` and `a` name the same entry
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Classify every entry of the old and new sets of (path, digest),
+/// the result is sorted by path
+pub fn audit(old: &[(PathBuf, Vec<u8>)], new: &[(PathBuf, Vec<u8>)]) -> Vec<Change> {
+    let old: BTreeMap<_, _> = old
+        .iter()
+        .map(|(path, digest)| (normalize(path), digest))
+        .collect();
+    let new: BTreeMap<_, _> = new
+        .iter()
+        .map(|(path, digest)| (normalize(path), digest))
+        .collect();
+
+    let mut changes = vec![];
+    // digests of old entries whose path is gone, in path order
+    let mut removed: HashMap<&Vec<u8>, Vec<&PathBuf>> = HashMap::new();
+    for (path, digest) in &old {
+        match new.get(path) {
+            Some(new_digest) if new_digest == digest => {
+                changes.push(Change::Unchanged(path.clone()))
+            }
+            Some(_) => changes.push(Change::Modified(path.clone())),
+            None => removed.entry(*digest).or_default().push(path),
+        }
+    }
+    for candidates in removed.values_mut() {
+        candidates.reverse();
+    }
+    for (path, digest) in &new {
+        if old.contains_key(path) {
+            continue;
+        }
+        match removed
+            .get_mut(*digest)
+            .and_then(|candidates| candidates.pop())
+        {
+            Some(from) => changes.push(Change::Moved {
+                from: from.clone(),
+                to: path.clone(),
+            }),
+            None => changes.push(Change::Added(path.clone())),
+        }
+    }
+    for paths in removed.into_values() {
+        changes.extend(paths.into_iter().map(|path| Change::Removed(path.clone())));
+    }
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+const KINDS: &[&str] = &["unchanged", "modified", "added", "removed", "moved"];
+
+/// Write one line per change followed by a count of each kind,
+/// unchanged entries are only listed when `verbose` is set
+pub fn write_report<W: Write>(changes: &[Change], verbose: bool, out: &mut W) -> io::Result<()> {
+    for change in changes {
+        match change {
+            Change::Unchanged(_) if !verbose => {}
+            Change::Moved { from, to } => writeln!(
+                out,
+                "{:<9}  {} -> {}",
+                "moved",
+                from.display(),
+                to.display()
+            )?,
+            change => writeln!(out, "{:<9}  {}", change.kind(), change.path().display())?,
+        }
+    }
+    let counts: Vec<_> = KINDS
+        .iter()
+        .map(|kind| {
+            let count = changes
+                .iter()
+                .filter(|change| change.kind() == *kind)
+                .count();
+            format!("{}: {}", kind, count)
+        })
+        .collect();
+    writeln!(out, "{}", counts.join(", "))
+}
+
+/// Write the changes as a JSON object with one array per kind,
+/// moves are listed as `{"from": ..., "to": ...}` objects
+pub fn write_json<W: Write>(changes: &[Change], out: &mut W) -> io::Result<()> {
+    writeln!(out, "{{")?;
+    for (i, kind) in KINDS.iter().enumerate() {
+        let entries: Vec<_> = changes
+            .iter()
+            .filter(|change| change.kind() == *kind)
+            .map(|change| match change {
+                Change::Moved { from, to } => format!(
+                    "{{\"from\": {}, \"to\": {}}}",
+                    json_string(&from.to_string_lossy()),
+                    json_string(&to.to_string_lossy())
+                ),
+                change => json_string(&change.path().to_string_lossy()),
+            })
+            .collect();
+        let separator = if i + 1 < KINDS.len() { "," } else { "" };
+        if entries.is_empty() {
+            writeln!(out, "  \"{}\": []{}", kind, separator)?;
+        } else {
+            writeln!(
+                out,
+                "  \"{}\": [\n    {}\n  ]{}",
+                kind,
+                entries.join(",\n    "),
+                separator
+            )?;
+        }
+    }
+    writeln!(out, "}}")
+}
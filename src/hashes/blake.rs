@@ -0,0 +1,146 @@
+use crate::utils::io::ByteRange;
+use crate::utils::progress_read::{style_bar, style_spinner, ProgressRead};
+use blake3::hazmat::{self, ChainingValue, HasherExt, Mode};
+use digest::{BlockInput, FixedOutput, Input, Reset};
+use generic_array::typenum::{U32, U64};
+use generic_array::GenericArray;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::path::Path;
+
+/// Inputs larger than this are split across the rayon pool
+const RAYON_THRESHOLD: usize = 128 * 1024;
+/// Bytes read by one worker in a row when a file is split, a power of two number
+/// of chunks so that each part is a complete subtree of the BLAKE3 tree
+const PART_SIZE: u64 = 16 * 1024 * 1024;
+
+/// BLAKE3 with a 256 bit output, adapted to the `Digest` interface
+#[derive(Clone, Default)]
+pub struct Blake3 {
+    inner: blake3::Hasher,
+}
+
+impl Input for Blake3 {
+    fn input<B: AsRef<[u8]>>(&mut self, data: B) {
+        let data = data.as_ref();
+        // large reads are hashed with BLAKE3's internal tree parallelism,
+        // so a single big file still uses every worker
+        if data.len() >= RAYON_THRESHOLD {
+            self.inner.update_rayon(data);
+        } else {
+            self.inner.update(data);
+        }
+    }
+}
+
+/// BLAKE3 compresses 64 byte blocks, which also makes it usable with HMAC
+impl BlockInput for Blake3 {
+    type BlockSize = U64;
+}
+
+impl FixedOutput for Blake3 {
+    type OutputSize = U32;
+
+    fn fixed_result(self) -> GenericArray<u8, Self::OutputSize> {
+        GenericArray::clone_from_slice(self.inner.finalize().as_bytes())
+    }
+}
+
+impl Reset for Blake3 {
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// Chaining value of the subtree made of `parts`, which cover `length` bytes
+fn subtree_cv(parts: &[ChainingValue], length: u64) -> ChainingValue {
+    if parts.len() == 1 {
+        return parts[0];
+    }
+    let left_length = hazmat::left_subtree_len(length);
+    let (left, right) = parts.split_at((left_length / PART_SIZE) as usize);
+    hazmat::merge_subtrees_non_root(
+        &subtree_cv(left, left_length),
+        &subtree_cv(right, length - left_length),
+        Mode::Hash,
+    )
+}
+
+/// Hash the range of a file with the rayon workers reading disjoint parts,
+/// progress of every part goes to the single bar `pb`. The digest is the
+/// BLAKE3 of the range, parts being subtrees merged up to the root
+pub fn hash_file(
+    path: &Path,
+    range: ByteRange,
+    pb: Option<ProgressBar>,
+    silent: bool,
+) -> Result<Vec<u8>, failure::Error> {
+    // opening the whole range checks the file and gives the length to split
+    let mut input = ProgressRead::from_file_part(path, range, pb.clone(), silent)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let length = match input.total_bytes() {
+        Some(length) => length,
+        None => {
+            // a FIFO can only be read once, from start to end
+            if let Some(bar) = &pb {
+                style_spinner(bar, &name);
+            }
+            let mut hasher = blake3::Hasher::new();
+            let result = input.for_each_chunk(|chunk| {
+                hasher.update(chunk);
+            });
+            if let Some(bar) = &pb {
+                match result {
+                    Ok(_) => bar.finish_with_message("Done."),
+                    Err(_) => bar.finish_and_clear(),
+                }
+            }
+            result?;
+            return Ok(hasher.finalize().as_bytes().to_vec());
+        }
+    };
+    drop(input);
+    if let Some(bar) = &pb {
+        style_bar(bar, &name, length);
+    }
+    let parts = length.div_ceil(PART_SIZE).max(1);
+    let hashers = (0..parts)
+        .into_par_iter()
+        .map(|part| {
+            let offset = part * PART_SIZE;
+            let part_range = ByteRange {
+                offset: range.offset + offset,
+                length: Some(PART_SIZE.min(length - offset)),
+            };
+            let mut input = ProgressRead::from_file_part(path, part_range, pb.clone(), silent)?;
+            let mut hasher = blake3::Hasher::new();
+            hasher.set_input_offset(offset);
+            input.for_each_chunk(|chunk| {
+                hasher.update(chunk);
+            })?;
+            Ok(hasher)
+        })
+        .collect::<Result<Vec<_>, failure::Error>>();
+    if let Some(bar) = &pb {
+        match hashers {
+            Ok(_) => bar.finish_with_message("Done."),
+            Err(_) => bar.finish_and_clear(),
+        }
+    }
+    let hashers = hashers?;
+    if let [hasher] = hashers.as_slice() {
+        return Ok(hasher.finalize().as_bytes().to_vec());
+    }
+    let parts: Vec<_> = hashers
+        .iter()
+        .map(|hasher| hasher.finalize_non_root())
+        .collect();
+    let left_length = hazmat::left_subtree_len(length);
+    let (left, right) = parts.split_at((left_length / PART_SIZE) as usize);
+    let root = hazmat::merge_subtrees_root(
+        &subtree_cv(left, left_length),
+        &subtree_cv(right, length - left_length),
+        Mode::Hash,
+    );
+    Ok(root.as_bytes().to_vec())
+}
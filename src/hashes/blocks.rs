@@ -0,0 +1,272 @@
+//! Piecewise digests: one digest per fixed-size block next to the whole-file digest.
+//!
+//! A block list is written like a checksum file, the whole file first and then one
+//! line per block in the style of hashdeep's piecewise mode:
+//! `<digest>  <path>` followed by `<digest>  <path> offset <start>-<end>`.
+use super::encoding::Encoding;
+use super::format::gnu_escape;
+use super::hasher::{Checksum, Hasher};
+use super::sums;
+use crate::utils::errors::ChecksumError;
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+
+/// Whole-file and per-block digests of a single file
+#[derive(Debug, Default)]
+pub struct BlockList {
+    pub path: PathBuf,
+    pub size: u64,
+    pub block_size: u64,
+    pub whole: Vec<u8>,
+    pub blocks: Vec<Vec<u8>>,
+}
+
+impl BlockList {
+    /// Inclusive byte range covered by the block at `index`
+    pub fn block_range(&self, index: usize) -> (u64, u64) {
+        let start = index as u64 * self.block_size;
+        let end = (start + self.block_size).min(self.size);
+        (start, end - 1)
+    }
+
+    pub fn write<W: Write>(&self, encoding: Encoding, out: &mut W) -> io::Result<()> {
+        let (escaped, name) = gnu_escape(&self.path.to_string_lossy());
+        let prefix = if escaped { "\\" } else { "" };
+        writeln!(out, "{}{}  {}", prefix, encoding.encode(&self.whole), name)?;
+        for (index, digest) in self.blocks.iter().enumerate() {
+            let (start, end) = self.block_range(index);
+            writeln!(
+                out,
+                "{}{}  {} offset {}-{}",
+                prefix,
+                encoding.encode(digest),
+                name,
+                start,
+                end
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Hash the whole input and each block of `block_size` bytes in a single read
+pub struct Blockwise<C, F> {
+    block_size: u64,
+    new_hasher: F,
+    whole: C,
+}
+
+impl<C: Checksum, F: Fn() -> C> Blockwise<C, F> {
+    pub fn new(block_size: u64, new_hasher: F) -> Self {
+        Self {
+            block_size,
+            whole: new_hasher(),
+            new_hasher,
+        }
+    }
+}
+
+impl<C: Checksum, F: Fn() -> C> Hasher for Blockwise<C, F> {
+    type OutputArray = BlockList;
+
+    fn from_reader<R: Read>(mut self, mut reader: R) -> Result<Self::OutputArray, failure::Error> {
+        let mut list = BlockList {
+            block_size: self.block_size,
+            ..BlockList::default()
+        };
+        let mut block = (self.new_hasher)();
+        // bytes already fed into the current block
+        let mut filled = 0;
+        let mut buffer = crate::utils::io::buffer();
+        loop {
+            let read_bytes = reader.read(&mut buffer)?;
+            if read_bytes == 0 {
+                break;
+            }
+            self.whole.update(&buffer[..read_bytes]);
+            list.size += read_bytes as u64;
+            let mut rest = &buffer[..read_bytes];
+            while !rest.is_empty() {
+                let take = rest.len().min((self.block_size - filled) as usize);
+                block.update(&rest[..take]);
+                filled += take as u64;
+                rest = &rest[take..];
+                if filled == self.block_size {
+                    let full = std::mem::replace(&mut block, (self.new_hasher)());
+                    list.blocks.push(full.finish().as_ref().to_vec());
+                    filled = 0;
+                }
+            }
+        }
+        if filled > 0 {
+            list.blocks.push(block.finish().as_ref().to_vec());
+        }
+        list.whole = self.whole.finish().as_ref().to_vec();
+        Ok(list)
+    }
+}
+
+/// Split `<path> offset <start>-<end>` into the path and the range
+fn split_offset(path: &str) -> Option<(&str, u64, u64)> {
+    let at = path.rfind(" offset ")?;
+    let (start, end) = path[at + 8..].split_once('-')?;
+    Some((&path[..at], start.parse().ok()?, end.parse().ok()?))
+}
+
+/// Read every block list of a file written by `BlockList::write`
+pub fn parse_block_lists<R: BufRead>(
+    reader: R,
+    encoding: Encoding,
+) -> Result<Vec<BlockList>, failure::Error> {
+    let sums_file = sums::parse_sums(reader, encoding)?;
+    let mut lists: Vec<BlockList> = vec![];
+    for entry in sums_file.entries {
+        let path = entry.path.to_string_lossy();
+        match split_offset(&path) {
+            Some((name, start, end)) => {
+                let list = lists
+                    .last_mut()
+                    .filter(|list| list.path.to_string_lossy() == name)
+                    .ok_or_else(|| {
+                        failure::format_err!("block of {} without its file line", name)
+                    })?;
+                if list.blocks.is_empty() {
+                    list.block_size = end - start + 1;
+                }
+                if start != list.size || end < start {
+                    Err(failure::format_err!(
+                        "blocks of {} are not contiguous",
+                        name
+                    ))?;
+                }
+                list.size = end + 1;
+                list.blocks.push(entry.digest);
+            }
+            None => lists.push(BlockList {
+                path: entry.path,
+                whole: entry.digest,
+                ..BlockList::default()
+            }),
+        }
+    }
+    Ok(lists)
+}
+
+/// Inclusive byte ranges where the two files differ, adjacent blocks are merged
+/// and bytes present in only one of the files count as different
+pub fn diff_ranges(old: &BlockList, new: &BlockList) -> Result<Vec<(u64, u64)>, failure::Error> {
+    if old.size == new.size && old.whole == new.whole {
+        return Ok(vec![]);
+    }
+    // the block size read back is the length of the first block, which is only
+    // shorter than the real block size when that block is the whole file
+    if !old.blocks.is_empty() && !new.blocks.is_empty() {
+        let (_, old_end) = old.block_range(0);
+        let (_, new_end) = new.block_range(0);
+        let shorter = if old_end < new_end { old } else { new };
+        if old_end != new_end && shorter.blocks.len() > 1 {
+            Err(ChecksumError::BlockSizeMismatch {
+                old: old.block_size,
+                new: new.block_size,
+            })?;
+        }
+    }
+    let mut ranges: Vec<(u64, u64)> = vec![];
+    let mut push = |start: u64, end: u64| match ranges.last_mut() {
+        Some(last) if last.1 + 1 == start => last.1 = end,
+        _ => ranges.push((start, end)),
+    };
+    let common = old.size.min(new.size);
+    for (index, (a, b)) in old.blocks.iter().zip(&new.blocks).enumerate() {
+        let (start, end) = old.block_range(index);
+        if start >= common {
+            break;
+        }
+        // a block cut short by the end of one file always differs
+        if a != b || end >= common {
+            push(start, end.min(common - 1));
+        }
+    }
+    if old.size != new.size {
+        push(common, old.size.max(new.size) - 1);
+    }
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_list(size: u64, block_size: u64, whole: u8, blocks: &[u8]) -> BlockList {
+        BlockList {
+            path: PathBuf::from("file"),
+            size,
+            block_size,
+            whole: vec![whole],
+            blocks: blocks.iter().map(|&block| vec![block]).collect(),
+        }
+    }
+
+    fn round_trip(list: &BlockList) -> BlockList {
+        let mut out = vec![];
+        list.write(Encoding::Hex, &mut out).unwrap();
+        let mut lists = parse_block_lists(&out[..], Encoding::Hex).unwrap();
+        assert_eq!(lists.len(), 1);
+        lists.pop().unwrap()
+    }
+
+    #[test]
+    fn parse_block_lists_reads_what_write_wrote() {
+        let list = round_trip(&block_list(10, 4, 0xaa, &[1, 2, 3]));
+        assert_eq!(list.path, PathBuf::from("file"));
+        assert_eq!((list.size, list.block_size), (10, 4));
+        assert_eq!(list.whole, vec![0xaa]);
+        assert_eq!(list.blocks, vec![vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn parse_block_lists_keeps_escaped_names() {
+        let mut list = block_list(4, 4, 0xaa, &[1]);
+        list.path = PathBuf::from("a\nb");
+        assert_eq!(round_trip(&list).path, PathBuf::from("a\nb"));
+    }
+
+    #[test]
+    fn parse_block_lists_rejects_orphan_and_gapped_blocks() {
+        let orphan = "01  file offset 0-3\n";
+        assert!(parse_block_lists(orphan.as_bytes(), Encoding::Hex).is_err());
+        let gapped = "aa  file\n01  file offset 0-3\n02  file offset 8-11\n";
+        assert!(parse_block_lists(gapped.as_bytes(), Encoding::Hex).is_err());
+    }
+
+    #[test]
+    fn diff_ranges_of_identical_files_is_empty() {
+        let list = block_list(10, 4, 0xaa, &[1, 2, 3]);
+        assert!(diff_ranges(&list, &list).unwrap().is_empty());
+    }
+
+    #[test]
+    fn diff_ranges_merges_adjacent_blocks() {
+        let old = block_list(12, 4, 0xaa, &[1, 2, 3]);
+        let new = block_list(12, 4, 0xbb, &[9, 9, 3]);
+        assert_eq!(diff_ranges(&old, &new).unwrap(), vec![(0, 7)]);
+    }
+
+    #[test]
+    fn diff_ranges_counts_bytes_of_the_longer_file() {
+        let old = block_list(6, 4, 0xaa, &[1, 2]);
+        let new = block_list(10, 4, 0xbb, &[1, 3, 4]);
+        assert_eq!(diff_ranges(&old, &new).unwrap(), vec![(4, 9)]);
+    }
+
+    #[test]
+    fn diff_ranges_rejects_different_block_sizes() {
+        let old = round_trip(&block_list(12, 4, 0xaa, &[1, 2, 3]));
+        let new = round_trip(&block_list(12, 6, 0xbb, &[1, 2]));
+        let err = diff_ranges(&old, &new).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChecksumError>(),
+            Some(ChecksumError::BlockSizeMismatch { old: 4, new: 6 })
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+//! On-disk cache of file digests.
+//!
+//! An entry is reused as long as the size, modification time, change time and inode
+//! of the file are the ones recorded along with the digest. Each line holds
+//! `<algorithm> <size> <mtime> <ctime> <inode> <hex digest> <path>` with times
+//! in nanoseconds and the path escaped like in checksum files.
+use super::encoding::Encoding;
+use super::format::gnu_escape;
+use super::sums::unescape_path;
+use crate::utils::io::{symlink_policy, write_atomically, SymlinkPolicy};
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+const HEADER: &str = "# crupter hash cache v1";
+
+/// What a file looked like when it was hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub size: u64,
+    pub mtime: i128,
+    pub ctime: i128,
+    pub inode: u64,
+}
+
+impl FileStamp {
+    #[cfg(unix)]
+    fn from_metadata(meta: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self {
+            size: meta.len(),
+            mtime: meta.mtime() as i128 * 1_000_000_000 + meta.mtime_nsec() as i128,
+            ctime: meta.ctime() as i128 * 1_000_000_000 + meta.ctime_nsec() as i128,
+            inode: meta.ino(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from_metadata(meta: &Metadata) -> Self {
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_nanos() as i128);
+        Self {
+            size: meta.len(),
+            mtime,
+            ctime: 0,
+            inode: 0,
+        }
+    }
+
+    /// Stamp of the file the path points to
+    pub fn of(path: &Path) -> io::Result<Self> {
+        fs::metadata(path).map(|meta| Self::from_metadata(&meta))
+    }
+
+    /// Stamp of a regular file, whose content can only change along with its stamp.
+    /// Devices, FIFOs and links hashed as links are not cached
+    pub fn of_cacheable(path: &Path) -> Option<Self> {
+        if symlink_policy() == SymlinkPolicy::NoFollow
+            && fs::symlink_metadata(path).ok()?.file_type().is_symlink()
+        {
+            return None;
+        }
+        let meta = fs::metadata(path).ok()?;
+        if meta.is_file() {
+            Some(Self::from_metadata(&meta))
+        } else {
+            None
+        }
+    }
+}
+
+/// Digests of one algorithm, shared by the parallel workers
+pub struct HashCache {
+    /// Where the cache is saved
+    path: PathBuf,
+    algorithm: String,
+    /// Entries of the algorithm by absolute path
+    entries: Mutex<HashMap<PathBuf, (FileStamp, Vec<u8>)>>,
+    /// Lines of other algorithms, written back untouched
+    others: Vec<String>,
+    /// Never return cached digests, only record new ones
+    rehash: bool,
+    changed: AtomicBool,
+}
+
+fn parse_line(line: &str) -> Option<(&str, PathBuf, FileStamp, Vec<u8>)> {
+    let mut fields = line.splitn(7, ' ');
+    let algorithm = fields.next()?;
+    let stamp = FileStamp {
+        size: fields.next()?.parse().ok()?,
+        mtime: fields.next()?.parse().ok()?,
+        ctime: fields.next()?.parse().ok()?,
+        inode: fields.next()?.parse().ok()?,
+    };
+    let digest = Encoding::Hex.decode(fields.next()?)?;
+    let path = unescape_path(fields.next()?)?;
+    Some((algorithm, PathBuf::from(path), stamp, digest))
+}
+
+/// Absolute form of the path so that entries do not depend on the working directory
+fn cache_key(path: &Path) -> io::Result<PathBuf> {
+    fs::canonicalize(path)
+}
+
+impl HashCache {
+    /// Load the entries of `algorithm`, a missing cache file is an empty cache
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        algorithm: &str,
+        rehash: bool,
+    ) -> Result<Self, failure::Error> {
+        let mut cache = Self {
+            path: path.as_ref().to_path_buf(),
+            algorithm: algorithm.to_owned(),
+            entries: Mutex::new(HashMap::new()),
+            others: vec![],
+            rehash,
+            changed: AtomicBool::new(false),
+        };
+        let file = match fs::File::open(&cache.path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(err) => return Err(err.into()),
+        };
+        let entries = cache.entries.get_mut().unwrap();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.starts_with('#') {
+                continue;
+            }
+            // unreadable lines are dropped, the digest is computed again
+            if let Some((line_algorithm, path, stamp, digest)) = parse_line(&line) {
+                if line_algorithm == algorithm {
+                    entries.insert(path, (stamp, digest));
+                } else {
+                    cache.others.push(line);
+                }
+            }
+        }
+        Ok(cache)
+    }
+
+    /// The cached digest of the file if it has not changed since it was hashed
+    pub fn get(&self, path: &Path) -> Option<Vec<u8>> {
+        if self.rehash {
+            return None;
+        }
+        let stamp = FileStamp::of_cacheable(path)?;
+        let key = cache_key(path).ok()?;
+        let entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some((cached, digest)) if *cached == stamp => Some(digest.clone()),
+            _ => None,
+        }
+    }
+
+    /// Record the digest of a file stamped with `stamp` before it was read,
+    /// nothing is stored if the file changed in the meantime
+    pub fn insert(&self, path: &Path, stamp: FileStamp, digest: &[u8]) {
+        if FileStamp::of_cacheable(path) != Some(stamp) {
+            return;
+        }
+        let key = match cache_key(path) {
+            Ok(key) => key,
+            Err(_) => return,
+        };
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (stamp, digest.to_vec()));
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Write the cache back if anything was added
+    pub fn save(&self) -> Result<(), failure::Error> {
+        if !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let mut out = vec![];
+        writeln!(out, "{}", HEADER)?;
+        for line in &self.others {
+            writeln!(out, "{}", line)?;
+        }
+        let entries = self.entries.lock().unwrap();
+        let mut paths: Vec<_> = entries.keys().collect();
+        paths.sort();
+        for path in paths {
+            let (stamp, digest) = &entries[path];
+            let (_, name) = gnu_escape(&path.to_string_lossy());
+            writeln!(
+                out,
+                "{} {} {} {} {} {} {}",
+                self.algorithm,
+                stamp.size,
+                stamp.mtime,
+                stamp.ctime,
+                stamp.inode,
+                Encoding::Hex.encode(digest),
+                name
+            )?;
+        }
+        write_atomically(&self.path, &out)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+//! Checkpoints of an interrupted hash: the hasher state and how far the file was read.
+//!
+//! A checkpoint is a small text file of `key value` lines. It records the file it
+//! belongs to, so that a checkpoint is never applied to another or a modified file.
+use super::cache::FileStamp;
+use super::encoding::Encoding;
+use super::format::gnu_escape_bytes;
+use super::sums::unescape_bytes;
+use crate::utils::io::{bytes_path, path_bytes, write_atomically};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "crupter-checkpoint 1";
+
+#[derive(Debug)]
+pub struct Checkpoint {
+    pub algorithm: String,
+    /// Absolute path of the hashed file
+    pub path: PathBuf,
+    /// The hashed file when hashing started
+    pub stamp: FileStamp,
+    /// Bytes consumed by the saved state
+    pub offset: u64,
+    /// State exported by `Resumable::export_state`
+    pub state: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// Read a checkpoint, None when the file does not exist. A file that is not a
+    /// checkpoint or is cut short is an `InvalidData` error
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed checkpoint file");
+        // the path is kept as raw bytes, the rest of the file is ASCII
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut lines = bytes.split(|&byte| byte == b'\n');
+        if lines.next() != Some(HEADER.as_bytes()) {
+            return Err(invalid());
+        }
+        let mut fields = std::collections::HashMap::new();
+        for line in lines {
+            if let Some(space) = line.iter().position(|&byte| byte == b' ') {
+                fields.insert(&line[..space], &line[space + 1..]);
+            }
+        }
+        let field = |key: &str| fields.get(key.as_bytes()).copied();
+        let text = |key: &str| std::str::from_utf8(field(key)?).ok();
+        let parse = || -> Option<Self> {
+            Some(Self {
+                algorithm: text("algorithm")?.to_string(),
+                path: bytes_path(unescape_bytes(field("path")?)?),
+                stamp: FileStamp {
+                    size: text("size")?.parse().ok()?,
+                    mtime: text("mtime")?.parse().ok()?,
+                    ctime: text("ctime")?.parse().ok()?,
+                    inode: text("inode")?.parse().ok()?,
+                },
+                offset: text("offset")?.parse().ok()?,
+                state: Encoding::Hex.decode(text("state")?)?,
+            })
+        };
+        parse().map(Some).ok_or_else(invalid)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = format!("{}\nalgorithm {}\npath ", HEADER, self.algorithm).into_bytes();
+        bytes.extend(gnu_escape_bytes(&path_bytes(&self.path)).1);
+        bytes.extend(
+            format!(
+                "\nsize {}\nmtime {}\nctime {}\ninode {}\noffset {}\nstate {}\n",
+                self.stamp.size,
+                self.stamp.mtime,
+                self.stamp.ctime,
+                self.stamp.inode,
+                self.offset,
+                Encoding::Hex.encode(&self.state)
+            )
+            .into_bytes(),
+        );
+        write_atomically(path, &bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("crupter-{}-{}", name, std::process::id()))
+    }
+
+    fn checkpoint(path: PathBuf) -> Checkpoint {
+        Checkpoint {
+            algorithm: "sha2-256".to_owned(),
+            path,
+            stamp: FileStamp {
+                size: 3 << 30,
+                mtime: 1_704_164_645_000_000_005,
+                ctime: -1,
+                inode: 42,
+            },
+            offset: 1 << 30,
+            state: vec![0, 1, 0xfe, 0xff],
+        }
+    }
+
+    #[test]
+    fn load_reads_what_save_wrote() {
+        let file = temp_path("checkpoint-round-trip");
+        let saved = checkpoint(PathBuf::from("/data/a\\b\nc"));
+        saved.save(&file).unwrap();
+        let loaded = Checkpoint::load(&file).unwrap().unwrap();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(loaded.algorithm, saved.algorithm);
+        assert_eq!(loaded.path, saved.path);
+        assert_eq!(loaded.stamp, saved.stamp);
+        assert_eq!(loaded.offset, saved.offset);
+        assert_eq!(loaded.state, saved.state);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn load_keeps_paths_that_are_not_utf8() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+        let file = temp_path("checkpoint-bytes");
+        let saved = checkpoint(PathBuf::from(OsString::from_vec(b"/data/\xff".to_vec())));
+        saved.save(&file).unwrap();
+        let loaded = Checkpoint::load(&file).unwrap().unwrap();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(loaded.path, saved.path);
+    }
+
+    #[test]
+    fn load_of_a_missing_file_is_none() {
+        let file = temp_path("checkpoint-missing");
+        assert!(Checkpoint::load(&file).unwrap().is_none());
+    }
+
+    #[test]
+    fn load_rejects_truncated_and_foreign_files() {
+        let file = temp_path("checkpoint-truncated");
+        checkpoint(PathBuf::from("/data/file")).save(&file).unwrap();
+        let content = fs::read(&file).unwrap();
+        let cut = content.len() - "state 0001feff\n".len();
+        for content in &[&content[..cut], b"not a checkpoint\n", b""] {
+            fs::write(&file, content).unwrap();
+            let err = Checkpoint::load(&file).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        fs::remove_file(&file).unwrap();
+    }
+}
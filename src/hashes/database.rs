@@ -0,0 +1,434 @@
+//! Integrity database of a directory tree, recording the digests and chosen attributes
+//! of every file so that later changes can be reported.
+//!
+//! The database is a text file starting with `# crupter integrity database v1` and
+//! the `# root`, `# attributes` and `# algorithms` header lines. Each following line
+//! holds the values of the attributes, the hex digests and the path relative to the
+//! root escaped like in checksum files, separated by spaces. A signed database ends
+//! with `# hmac-sha2-256 <hex>`, the HMAC of every byte before that line.
+use super::encoding::Encoding;
+use super::format::gnu_escape;
+use super::hasher::Checksum;
+use super::hmac::HmacChecksum;
+use super::sums::unescape_path;
+use super::Sha256;
+use crate::utils::errors::{ArgumentError, ChecksumError};
+use crate::utils::io::write_atomically;
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const HEADER: &str = "# crupter integrity database v1";
+const SIGNATURE: &str = "# hmac-sha2-256 ";
+
+/// Names of all attributes that can be tracked
+pub const ATTRIBUTES: &[&str] = &["size", "mode", "owner", "mtime"];
+
+/// What to do with a database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbCommand {
+    /// Record a tree into a new database
+    Init,
+    /// Report the changes since the database was recorded
+    Check,
+    /// Report the changes and record the current state
+    Update,
+}
+
+/// File attribute recorded in a database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    /// Size in bytes
+    Size,
+    /// File type and permission bits, in octal
+    Mode,
+    /// User and group IDs as `uid:gid`
+    Owner,
+    /// Modification time in nanoseconds
+    Mtime,
+}
+
+impl FromStr for Attribute {
+    type Err = failure::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "size" => Attribute::Size,
+            "mode" => Attribute::Mode,
+            "owner" => Attribute::Owner,
+            "mtime" => Attribute::Mtime,
+            _ => Err(ArgumentError::UnknownAttribute {
+                name: s.to_owned(),
+                supported: ATTRIBUTES.join(", "),
+            })?,
+        })
+    }
+}
+
+impl Attribute {
+    pub fn name(self) -> &'static str {
+        match self {
+            Attribute::Size => "size",
+            Attribute::Mode => "mode",
+            Attribute::Owner => "owner",
+            Attribute::Mtime => "mtime",
+        }
+    }
+
+    /// Value of the attribute as written in the database
+    #[cfg(unix)]
+    pub fn value(self, meta: &Metadata) -> String {
+        use std::os::unix::fs::MetadataExt;
+        match self {
+            Attribute::Size => meta.len().to_string(),
+            Attribute::Mode => format!("{:o}", meta.mode()),
+            Attribute::Owner => format!("{}:{}", meta.uid(), meta.gid()),
+            Attribute::Mtime => {
+                (meta.mtime() as i128 * 1_000_000_000 + meta.mtime_nsec() as i128).to_string()
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn value(self, meta: &Metadata) -> String {
+        match self {
+            Attribute::Size => meta.len().to_string(),
+            Attribute::Mode => if meta.permissions().readonly() {
+                "444"
+            } else {
+                "644"
+            }
+            .to_owned(),
+            Attribute::Owner => "0:0".to_owned(),
+            Attribute::Mtime => meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |time| time.as_nanos())
+                .to_string(),
+        }
+    }
+}
+
+/// What is recorded of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRecord {
+    /// Values of the database attributes, in their order
+    pub values: Vec<String>,
+    /// Digests of the database algorithms, in their order
+    pub digests: Vec<Vec<u8>>,
+}
+
+/// How a file differs from its record
+#[derive(Debug, PartialEq, Eq)]
+pub enum Drift {
+    Added,
+    Removed,
+    /// Names of the attributes and algorithms that differ
+    Changed(Vec<String>),
+}
+
+impl Drift {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Drift::Added => "added",
+            Drift::Removed => "removed",
+            Drift::Changed(_) => "changed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Database {
+    /// Directory the paths are relative to
+    pub root: PathBuf,
+    pub attributes: Vec<Attribute>,
+    pub algorithms: Vec<String>,
+    /// Records by path relative to the root
+    pub records: BTreeMap<PathBuf, FileRecord>,
+}
+
+fn signature(content: &[u8], key: &[u8]) -> String {
+    let mut mac = HmacChecksum::<Sha256>::new(key);
+    mac.update(content);
+    Encoding::Hex.encode(&mac.finish())
+}
+
+/// Whether `signed` is the hex signature of `content`, compared in constant time
+fn is_signed_by(content: &[u8], key: &[u8], signed: &str) -> bool {
+    let signed = match Encoding::Hex.decode(signed) {
+        Some(signed) => signed,
+        None => return false,
+    };
+    let mut mac = HmacChecksum::<Sha256>::new(key);
+    mac.update(content);
+    mac.verify(&signed)
+}
+
+fn escape_path(path: &Path) -> String {
+    gnu_escape(&path.to_string_lossy()).1
+}
+
+impl Database {
+    pub fn new(root: PathBuf, attributes: Vec<Attribute>, algorithms: Vec<String>) -> Self {
+        Self {
+            root,
+            attributes,
+            algorithms,
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// Record of a file from its metadata and the digests of every algorithm
+    pub fn record(&self, meta: &Metadata, digests: Vec<Vec<u8>>) -> FileRecord {
+        FileRecord {
+            values: self
+                .attributes
+                .iter()
+                .map(|attribute| attribute.value(meta))
+                .collect(),
+            digests,
+        }
+    }
+
+    /// Write the database, signed when a key is given
+    pub fn save(&self, path: &Path, key: Option<&[u8]>) -> Result<(), failure::Error> {
+        let join = |names: Vec<&str>| names.join(",");
+        let mut out = vec![];
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "# root {}", escape_path(&self.root))?;
+        writeln!(
+            out,
+            "# attributes {}",
+            join(
+                self.attributes
+                    .iter()
+                    .map(|attribute| attribute.name())
+                    .collect()
+            )
+        )?;
+        writeln!(
+            out,
+            "# algorithms {}",
+            join(self.algorithms.iter().map(String::as_str).collect())
+        )?;
+        for (path, record) in &self.records {
+            for value in &record.values {
+                write!(out, "{} ", value)?;
+            }
+            for digest in &record.digests {
+                write!(out, "{} ", Encoding::Hex.encode(digest))?;
+            }
+            writeln!(out, "{}", escape_path(path))?;
+        }
+        if let Some(key) = key {
+            let signature = signature(&out, key);
+            writeln!(out, "{}{}", SIGNATURE, signature)?;
+        }
+        write_atomically(path, &out)?;
+        Ok(())
+    }
+
+    /// Read a database. When a key is given the database must be signed with it,
+    /// and a signed database can only be read with its key
+    pub fn load(path: &Path, key: Option<&[u8]>) -> Result<Self, failure::Error> {
+        let file = path.display().to_string();
+        let invalid = || ChecksumError::InvalidDatabase { file: file.clone() };
+        let content = std::fs::read(path)?;
+        let content = String::from_utf8(content).map_err(|_| invalid())?;
+
+        // the signature covers everything up to its own line
+        let (body, signed) = match content.rfind(SIGNATURE) {
+            Some(start) if content[..start].is_empty() || content[..start].ends_with('\n') => (
+                &content[..start],
+                Some(content[start + SIGNATURE.len()..].trim()),
+            ),
+            _ => (content.as_str(), None),
+        };
+        match (key, signed) {
+            (Some(key), Some(signed)) if is_signed_by(body.as_bytes(), key, signed) => {}
+            (Some(_), _) => Err(ChecksumError::DatabaseTampered { file: file.clone() })?,
+            (None, Some(_)) => Err(ChecksumError::DatabaseSigned { file: file.clone() })?,
+            (None, None) => {}
+        }
+
+        let mut lines = body.lines();
+        if lines.next() != Some(HEADER) {
+            Err(invalid())?;
+        }
+        let mut header = |name: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix("# "))
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|line| line.strip_prefix(' '))
+                .map(str::to_owned)
+                .ok_or_else(invalid)
+        };
+        let root = unescape_path(&header("root")?).ok_or_else(invalid)?;
+        let attributes = header("attributes")?
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Attribute>, _>>()?;
+        let algorithms: Vec<String> = header("algorithms")?
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+            .collect();
+        let mut database = Database::new(PathBuf::from(root), attributes, algorithms);
+        for line in lines {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns = database.attributes.len() + database.algorithms.len();
+            let mut fields = line.splitn(columns + 1, ' ');
+            let values = (&mut fields)
+                .take(database.attributes.len())
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            let digests = (&mut fields)
+                .take(database.algorithms.len())
+                .map(|digest| Encoding::Hex.decode(digest))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(invalid)?;
+            let path = fields.next().and_then(unescape_path).ok_or_else(invalid)?;
+            if values.len() + digests.len() != columns {
+                Err(invalid())?;
+            }
+            database
+                .records
+                .insert(PathBuf::from(path), FileRecord { values, digests });
+        }
+        Ok(database)
+    }
+
+    /// Files that differ between this database and `current`, taken with the same
+    /// attributes and algorithms, in path order
+    pub fn drift(&self, current: &Database) -> Vec<(PathBuf, Drift)> {
+        let mut drift = vec![];
+        for (path, record) in &self.records {
+            match current.records.get(path) {
+                None => drift.push((path.clone(), Drift::Removed)),
+                Some(now) if now != record => {
+                    let attributes = self
+                        .attributes
+                        .iter()
+                        .zip(record.values.iter().zip(&now.values))
+                        .filter(|(_, (old, new))| old != new)
+                        .map(|(attribute, _)| attribute.name().to_owned());
+                    let algorithms = self
+                        .algorithms
+                        .iter()
+                        .zip(record.digests.iter().zip(&now.digests))
+                        .filter(|(_, (old, new))| old != new)
+                        .map(|(algorithm, _)| algorithm.clone());
+                    drift.push((
+                        path.clone(),
+                        Drift::Changed(attributes.chain(algorithms).collect()),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        for path in current.records.keys() {
+            if !self.records.contains_key(path) {
+                drift.push((path.clone(), Drift::Added));
+            }
+        }
+        drift.sort_by(|(left, _), (right, _)| left.cmp(right));
+        drift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("crupter-{}-{}", name, std::process::id()))
+    }
+
+    fn database() -> Database {
+        let mut database = Database::new(
+            PathBuf::from("/data"),
+            vec![Attribute::Size, Attribute::Mode],
+            vec!["sha2-256".to_owned()],
+        );
+        for (path, size) in &[("a", "1"), ("dir/b c", "2"), ("new\nline", "3")] {
+            database.records.insert(
+                PathBuf::from(path),
+                FileRecord {
+                    values: vec![size.to_string(), "100644".to_owned()],
+                    digests: vec![vec![0xab; 32]],
+                },
+            );
+        }
+        database
+    }
+
+    fn assert_error(result: Result<Database, failure::Error>, check: fn(&ChecksumError) -> bool) {
+        let err = result.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(err) if check(err)),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn load_reads_what_save_wrote() {
+        let file = temp_path("database-round-trip");
+        let saved = database();
+        saved.save(&file, None).unwrap();
+        let loaded = Database::load(&file, None).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(loaded.root, saved.root);
+        assert_eq!(loaded.attributes, saved.attributes);
+        assert_eq!(loaded.algorithms, saved.algorithms);
+        assert_eq!(loaded.records, saved.records);
+        assert!(saved.drift(&loaded).is_empty());
+    }
+
+    #[test]
+    fn load_checks_the_signature() {
+        let file = temp_path("database-signed");
+        database().save(&file, Some(b"key")).unwrap();
+        assert!(Database::load(&file, Some(b"key")).is_ok());
+        assert_error(Database::load(&file, Some(b"other")), |err| {
+            matches!(err, ChecksumError::DatabaseTampered { .. })
+        });
+        assert_error(Database::load(&file, None), |err| {
+            matches!(err, ChecksumError::DatabaseSigned { .. })
+        });
+
+        let content = fs::read_to_string(&file).unwrap();
+        fs::write(&file, content.replacen("100644", "100755", 1)).unwrap();
+        assert_error(Database::load(&file, Some(b"key")), |err| {
+            matches!(err, ChecksumError::DatabaseTampered { .. })
+        });
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_malformed_databases() {
+        let file = temp_path("database-malformed");
+        database().save(&file, None).unwrap();
+        let content = fs::read_to_string(&file).unwrap();
+        let malformed = [
+            content.replacen(HEADER, "# something else", 1),
+            content.replacen("# algorithms", "# digests", 1),
+            content.replacen(&"ab".repeat(32), "zz", 1),
+            content.trim_end().rsplit_once(' ').unwrap().0.to_owned(),
+        ];
+        for content in &malformed {
+            fs::write(&file, content).unwrap();
+            assert_error(Database::load(&file, None), |err| {
+                matches!(err, ChecksumError::InvalidDatabase { .. })
+            });
+        }
+        fs::remove_file(&file).unwrap();
+    }
+}
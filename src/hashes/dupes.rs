@@ -0,0 +1,221 @@
+//! Duplicate file search. Candidates are grouped by size, then by a digest of their
+//! first and last blocks, and only files still sharing a group are hashed in full.
+use super::cache::FileStamp;
+use super::encoding::Encoding;
+use super::format::json_string;
+use super::hasher::Checksum;
+use crate::utils::io::file_id;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes read at each end of a file for its partial digest
+pub const PARTIAL_BLOCK: u64 = 4096;
+
+/// What to do with the duplicates of the first file of each set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DupesAction {
+    /// Replace duplicates with hard links to the first file
+    Hardlink,
+    /// Remove duplicates
+    Delete,
+}
+
+/// Files found with the same content
+#[derive(Debug)]
+pub struct DuplicateSet {
+    pub size: u64,
+    pub digest: Vec<u8>,
+    /// Sorted, the first one is kept by the actions
+    pub files: Vec<PathBuf>,
+}
+
+impl DuplicateSet {
+    /// Space taken by every copy but one
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+/// Files of the same size
+pub type SizeGroup = (u64, Vec<PathBuf>);
+
+/// Group files by size, keeping only sizes shared by several files. Empty files
+/// waste no space and are left out, as are other names of a file already listed.
+/// Files whose metadata can not be read are returned apart
+pub fn group_by_size(files: Vec<PathBuf>) -> (Vec<SizeGroup>, Vec<(PathBuf, io::Error)>) {
+    let mut sizes: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut errors = vec![];
+    for file in files {
+        match fs::metadata(&file) {
+            Ok(meta) if !meta.is_file() || meta.len() == 0 => {}
+            Ok(meta) => {
+                let first_name = match file_id(&meta) {
+                    Some(id) => seen.insert(id),
+                    None => true,
+                };
+                if first_name {
+                    sizes.entry(meta.len()).or_default().push(file);
+                }
+            }
+            Err(err) => errors.push((file, err)),
+        }
+    }
+    let groups = sizes
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(size, mut files)| {
+            files.sort();
+            (size, files)
+        })
+        .collect();
+    (groups, errors)
+}
+
+/// Whether the partial digest of a file of `size` bytes covers all of it,
+/// in which case it is also the full digest
+pub fn is_whole(size: u64) -> bool {
+    size <= 2 * PARTIAL_BLOCK
+}
+
+/// Digest of the first and last `PARTIAL_BLOCK` bytes of a file of `size` bytes
+pub fn partial_digest<C: Checksum>(path: &Path, size: u64, mut hasher: C) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; PARTIAL_BLOCK as usize];
+    let head = size.min(PARTIAL_BLOCK) as usize;
+    file.read_exact(&mut buf[..head])?;
+    hasher.update(&buf[..head]);
+    if size > PARTIAL_BLOCK {
+        let tail_start = PARTIAL_BLOCK.max(size - PARTIAL_BLOCK);
+        let tail = (size - tail_start) as usize;
+        file.seek(SeekFrom::Start(tail_start))?;
+        file.read_exact(&mut buf[..tail])?;
+        hasher.update(&buf[..tail]);
+    }
+    Ok(hasher.finish().as_ref().to_vec())
+}
+
+/// Group files by digest, keeping digests shared by several files in the order
+/// of their first file
+pub fn group_by_digest(entries: Vec<(PathBuf, Vec<u8>)>) -> Vec<(Vec<u8>, Vec<PathBuf>)> {
+    let mut groups: Vec<(Vec<u8>, Vec<PathBuf>)> = vec![];
+    for (file, digest) in entries {
+        match groups.iter_mut().find(|(group, _)| *group == digest) {
+            Some((_, files)) => files.push(file),
+            None => groups.push((digest, vec![file])),
+        }
+    }
+    groups.retain(|(_, files)| files.len() > 1);
+    groups
+}
+
+/// Replace `duplicate` with a hard link to `original`, through a temporary
+/// name so the duplicate is never missing
+pub fn hardlink(original: &Path, duplicate: &Path) -> io::Result<()> {
+    let mut temporary = duplicate.as_os_str().to_owned();
+    temporary.push(".crupter-link");
+    let temporary = PathBuf::from(temporary);
+    fs::hard_link(original, &temporary)?;
+    let renamed = fs::rename(&temporary, duplicate);
+    if renamed.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    renamed
+}
+
+/// Whether two files hold the same bytes
+pub fn same_content(left: &Path, right: &Path) -> io::Result<bool> {
+    let mut left = BufReader::new(File::open(left)?);
+    let mut right = BufReader::new(File::open(right)?);
+    loop {
+        let (left_buf, right_buf) = (left.fill_buf()?, right.fill_buf()?);
+        let length = left_buf.len().min(right_buf.len());
+        if length == 0 {
+            return Ok(left_buf.is_empty() && right_buf.is_empty());
+        }
+        if left_buf[..length] != right_buf[..length] {
+            return Ok(false);
+        }
+        left.consume(length);
+        right.consume(length);
+    }
+}
+
+/// Whether `duplicate` can be replaced by or removed in favour of `original`: it
+/// still has the size and modification time of `stamp`, taken before it was
+/// hashed, and the same bytes as `original`. Digests alone are not trusted since
+/// the fast non-cryptographic ones do collide
+pub fn still_duplicate(original: &Path, duplicate: &Path, stamp: &FileStamp) -> io::Result<bool> {
+    let now = FileStamp::of(duplicate)?;
+    if now.size != stamp.size || now.mtime != stamp.mtime {
+        return Ok(false);
+    }
+    same_content(original, duplicate)
+}
+
+/// Write each set with its digest, then a summary of the wasted space
+pub fn write_report<W: Write>(
+    sets: &[DuplicateSet],
+    encoding: Encoding,
+    out: &mut W,
+) -> io::Result<()> {
+    for set in sets {
+        writeln!(
+            out,
+            "{}  {} files of {} bytes, {} bytes wasted",
+            encoding.encode(&set.digest),
+            set.files.len(),
+            set.size,
+            set.wasted()
+        )?;
+        for file in &set.files {
+            writeln!(out, "    {}", file.display())?;
+        }
+    }
+    let duplicates: usize = sets.iter().map(|set| set.files.len() - 1).sum();
+    let wasted: u64 = sets.iter().map(DuplicateSet::wasted).sum();
+    writeln!(
+        out,
+        "duplicate sets: {}, duplicate files: {}, wasted: {} ({} bytes)",
+        sets.len(),
+        duplicates,
+        indicatif::HumanBytes(wasted),
+        wasted
+    )
+}
+
+/// Write the sets as a JSON object with the total wasted bytes
+pub fn write_json<W: Write>(
+    sets: &[DuplicateSet],
+    encoding: Encoding,
+    out: &mut W,
+) -> io::Result<()> {
+    let entries: Vec<_> = sets
+        .iter()
+        .map(|set| {
+            let files: Vec<_> = set
+                .files
+                .iter()
+                .map(|file| json_string(&file.to_string_lossy()))
+                .collect();
+            format!(
+                "{{\"digest\": {}, \"size\": {}, \"wasted\": {}, \"files\": [{}]}}",
+                json_string(&encoding.encode(&set.digest)),
+                set.size,
+                set.wasted(),
+                files.join(", ")
+            )
+        })
+        .collect();
+    let wasted: u64 = sets.iter().map(DuplicateSet::wasted).sum();
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"wasted\": {},", wasted)?;
+    if entries.is_empty() {
+        writeln!(out, "  \"sets\": []")?;
+    } else {
+        writeln!(out, "  \"sets\": [\n    {}\n  ]", entries.join(",\n    "))?;
+    }
+    writeln!(out, "}}")
+}
@@ -0,0 +1,62 @@
+//! Text encodings of digests.
+use crate::utils::errors::ChecksumError;
+use data_encoding::{BASE32, BASE64, BASE64URL_NOPAD, HEXLOWER_PERMISSIVE, HEXUPPER};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Lowercase hex, two digits per byte
+    #[default]
+    Hex,
+    /// Uppercase hex, two digits per byte
+    UpperHex,
+    /// Standard base64 with padding
+    Base64,
+    /// URL and filename safe base64 without padding
+    Base64Url,
+    /// RFC 4648 base32 with padding
+    Base32,
+}
+
+pub const ENCODINGS: &[&str] = &["hex", "upper-hex", "base64", "base64url", "base32"];
+
+impl FromStr for Encoding {
+    type Err = failure::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "hex" => Encoding::Hex,
+            "upper-hex" => Encoding::UpperHex,
+            "base64" => Encoding::Base64,
+            "base64url" => Encoding::Base64Url,
+            "base32" => Encoding::Base32,
+            _ => Err(ChecksumError::UnknownEncoding {
+                name: s.to_owned(),
+                supported: ENCODINGS.join(", "),
+            })?,
+        })
+    }
+}
+
+impl Encoding {
+    pub fn encode(self, digest: &[u8]) -> String {
+        match self {
+            Encoding::Hex => HEXLOWER_PERMISSIVE.encode(digest),
+            Encoding::UpperHex => HEXUPPER.encode(digest),
+            Encoding::Base64 => BASE64.encode(digest),
+            Encoding::Base64Url => BASE64URL_NOPAD.encode(digest),
+            Encoding::Base32 => BASE32.encode(digest),
+        }
+    }
+
+    /// Decode a digest, hex is accepted in either case
+    pub fn decode(self, text: &str) -> Option<Vec<u8>> {
+        let text = text.as_bytes();
+        match self {
+            Encoding::Hex | Encoding::UpperHex => HEXLOWER_PERMISSIVE.decode(text),
+            Encoding::Base64 => BASE64.decode(text),
+            Encoding::Base64Url => BASE64URL_NOPAD.decode(text),
+            Encoding::Base32 => BASE32.decode(text),
+        }
+        .ok()
+    }
+}
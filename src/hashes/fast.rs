@@ -0,0 +1,170 @@
+//! Non-cryptographic checksums, commonly stored in manifests and SFV files.
+//! Results are given in big-endian byte order, matching how other tools print them.
+use super::hasher::{Checksum, Resumable};
+use std::convert::TryInto;
+
+static CRC_64: crc::Crc<u64> = crc::Crc::<u64>::new(&crc::CRC_64_XZ);
+
+/// CRC-32 (IEEE 802.3), as used by zlib, zip and SFV
+#[derive(Clone, Default)]
+pub struct Crc32 {
+    inner: crc32fast::Hasher,
+}
+
+impl Checksum for Crc32 {
+    type Output = [u8; 4];
+
+    fn update(&mut self, buf: &[u8]) {
+        self.inner.update(buf);
+    }
+    fn finish(self) -> Self::Output {
+        self.inner.finalize().to_be_bytes()
+    }
+    fn output_size(&self) -> usize {
+        4
+    }
+}
+
+/// The state of a 32 bit checksum is its current value
+fn import_u32(state: &[u8]) -> Option<u32> {
+    state.try_into().ok().map(u32::from_be_bytes)
+}
+
+impl Resumable for Crc32 {
+    fn export_state(&self) -> Vec<u8> {
+        self.inner.clone().finalize().to_be_bytes().to_vec()
+    }
+    fn import_state(&mut self, state: &[u8]) -> bool {
+        import_u32(state)
+            .map(|crc| self.inner = crc32fast::Hasher::new_with_initial(crc))
+            .is_some()
+    }
+}
+
+/// CRC-32C (Castagnoli), as used by iSCSI, ext4 and cloud storage
+#[derive(Clone, Default)]
+pub struct Crc32c {
+    crc: u32,
+}
+
+impl Checksum for Crc32c {
+    type Output = [u8; 4];
+
+    fn update(&mut self, buf: &[u8]) {
+        self.crc = crc32c::crc32c_append(self.crc, buf);
+    }
+    fn finish(self) -> Self::Output {
+        self.crc.to_be_bytes()
+    }
+    fn output_size(&self) -> usize {
+        4
+    }
+}
+
+impl Resumable for Crc32c {
+    fn export_state(&self) -> Vec<u8> {
+        self.crc.to_be_bytes().to_vec()
+    }
+    fn import_state(&mut self, state: &[u8]) -> bool {
+        import_u32(state).map(|crc| self.crc = crc).is_some()
+    }
+}
+
+/// CRC-64 with the ECMA-182 polynomial in its XZ variant
+#[derive(Clone)]
+pub struct Crc64 {
+    inner: crc::Digest<'static, u64>,
+}
+
+impl Default for Crc64 {
+    fn default() -> Self {
+        Self {
+            inner: CRC_64.digest(),
+        }
+    }
+}
+
+impl Checksum for Crc64 {
+    type Output = [u8; 8];
+
+    fn update(&mut self, buf: &[u8]) {
+        self.inner.update(buf);
+    }
+    fn finish(self) -> Self::Output {
+        self.inner.finalize().to_be_bytes()
+    }
+    fn output_size(&self) -> usize {
+        8
+    }
+}
+
+/// 64 bit XXH3
+#[derive(Clone, Default)]
+pub struct Xxh3 {
+    inner: xxhash_rust::xxh3::Xxh3,
+}
+
+impl Checksum for Xxh3 {
+    type Output = [u8; 8];
+
+    fn update(&mut self, buf: &[u8]) {
+        self.inner.update(buf);
+    }
+    fn finish(self) -> Self::Output {
+        self.inner.digest().to_be_bytes()
+    }
+    fn output_size(&self) -> usize {
+        8
+    }
+}
+
+/// 128 bit XXH3
+#[derive(Clone, Default)]
+pub struct Xxh128 {
+    inner: xxhash_rust::xxh3::Xxh3,
+}
+
+impl Checksum for Xxh128 {
+    type Output = [u8; 16];
+
+    fn update(&mut self, buf: &[u8]) {
+        self.inner.update(buf);
+    }
+    fn finish(self) -> Self::Output {
+        self.inner.digest128().to_be_bytes()
+    }
+    fn output_size(&self) -> usize {
+        16
+    }
+}
+
+/// Adler-32, as used by zlib streams
+#[derive(Clone, Default)]
+pub struct Adler32 {
+    inner: adler::Adler32,
+}
+
+impl Checksum for Adler32 {
+    type Output = [u8; 4];
+
+    fn update(&mut self, buf: &[u8]) {
+        self.inner.write_slice(buf);
+    }
+    fn finish(self) -> Self::Output {
+        self.inner.checksum().to_be_bytes()
+    }
+    fn output_size(&self) -> usize {
+        4
+    }
+}
+
+impl Resumable for Adler32 {
+    fn export_state(&self) -> Vec<u8> {
+        self.inner.checksum().to_be_bytes().to_vec()
+    }
+    fn import_state(&mut self, state: &[u8]) -> bool {
+        import_u32(state)
+            .map(|sum| self.inner = adler::Adler32::from_checksum(sum))
+            .is_some()
+    }
+}
@@ -0,0 +1,223 @@
+//! Formatting of hash results in formats other tools can read back.
+use super::encoding::Encoding;
+use crate::utils::errors::ChecksumError;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `<hex>  <path>`, as written by `sha256sum`
+    Gnu,
+    /// `SHA256 (<path>) = <hex>`, as written by `shasum --tag` and BSD `sha256`
+    Bsd,
+    /// `<path> <hex>`, simple file verification
+    Sfv,
+    Json,
+    Csv,
+}
+
+pub const OUTPUT_FORMATS: &[&str] = &["gnu", "bsd", "sfv", "json", "csv"];
+
+impl FromStr for OutputFormat {
+    type Err = failure::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "gnu" => OutputFormat::Gnu,
+            "bsd" => OutputFormat::Bsd,
+            "sfv" => OutputFormat::Sfv,
+            "json" => OutputFormat::Json,
+            "csv" => OutputFormat::Csv,
+            _ => Err(ChecksumError::UnknownFormat {
+                name: s.to_owned(),
+                supported: OUTPUT_FORMATS.join(", "),
+            })?,
+        })
+    }
+}
+
+/// Result of hashing one input with one algorithm
+#[derive(Debug)]
+pub struct HashRecord {
+    /// The hashed file, None for stdin
+    pub path: Option<PathBuf>,
+    pub algorithm: String,
+    /// Digest bytes or the reason hashing failed
+    pub result: Result<Vec<u8>, String>,
+    /// Size of the input in bytes when known
+    pub size: Option<u64>,
+    pub elapsed: Duration,
+}
+
+impl HashRecord {
+    fn path_string(&self) -> String {
+        self.path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|| "-".to_owned())
+    }
+}
+
+/// Tag used by the BSD format for an algorithm name
+pub fn bsd_tag(algorithm: &str) -> String {
+    match algorithm {
+        "sha2-224" | "sha224" => "SHA224".to_owned(),
+        "sha2-256" | "sha256" => "SHA256".to_owned(),
+        "sha2-384" | "sha384" => "SHA384".to_owned(),
+        "sha2-512" | "sha512" => "SHA512".to_owned(),
+        "blake2b" => "BLAKE2b".to_owned(),
+        "blake2s" => "BLAKE2s".to_owned(),
+        _ => algorithm.to_uppercase(),
+    }
+}
+
+/// Escape a name the way `sha256sum` does, returns whether escaping was needed
+pub fn gnu_escape(name: &str) -> (bool, String) {
+    if !name.contains(&['\\', '\n', '\r'][..]) {
+        return (false, name.to_owned());
+    }
+    let escaped = name
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    (true, escaped)
+}
+
+/// Same as `gnu_escape` on the raw bytes of a name, which need not be UTF-8
+pub fn gnu_escape_bytes(name: &[u8]) -> (bool, Vec<u8>) {
+    if !name
+        .iter()
+        .any(|byte| matches!(byte, b'\\' | b'\n' | b'\r'))
+    {
+        return (false, name.to_vec());
+    }
+    let mut escaped = Vec::with_capacity(name.len() + 2);
+    for &byte in name {
+        match byte {
+            b'\\' => escaped.extend_from_slice(b"\\\\"),
+            b'\n' => escaped.extend_from_slice(b"\\n"),
+            b'\r' => escaped.extend_from_slice(b"\\r"),
+            _ => escaped.push(byte),
+        }
+    }
+    (true, escaped)
+}
+
+/// Quote and escape a string as a JSON string literal
+pub fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Write the records in the given format.
+/// Text formats send failures to `errors` so that `out` stays readable by other tools,
+/// JSON and CSV keep failures next to the results.
+pub fn write_records<W: Write, E: Write>(
+    format: OutputFormat,
+    encoding: Encoding,
+    records: &[HashRecord],
+    out: &mut W,
+    errors: &mut E,
+) -> std::io::Result<()> {
+    if format == OutputFormat::Json {
+        writeln!(out, "[")?;
+    } else if format == OutputFormat::Csv {
+        writeln!(out, "path,algorithm,digest,size,elapsed,error")?;
+    }
+    for (index, record) in records.iter().enumerate() {
+        let path = record.path_string();
+        match format {
+            OutputFormat::Json => {
+                let (digest, error) = match &record.result {
+                    Ok(digest) => (json_string(&encoding.encode(digest)), "null".to_owned()),
+                    Err(err) => ("null".to_owned(), json_string(err)),
+                };
+                let size = record
+                    .size
+                    .map(|size| size.to_string())
+                    .unwrap_or_else(|| "null".to_owned());
+                let separator = if index + 1 < records.len() { "," } else { "" };
+                writeln!(
+                    out,
+                    "  {{\"path\": {}, \"algorithm\": {}, \"digest\": {}, \"size\": {}, \"elapsed\": {:.6}, \"error\": {}}}{}",
+                    json_string(&path),
+                    json_string(&record.algorithm),
+                    digest,
+                    size,
+                    record.elapsed.as_secs_f64(),
+                    error,
+                    separator
+                )?;
+            }
+            OutputFormat::Csv => {
+                let (digest, error) = match &record.result {
+                    Ok(digest) => (encoding.encode(digest), String::new()),
+                    Err(err) => (String::new(), err.clone()),
+                };
+                writeln!(
+                    out,
+                    "{},{},{},{},{:.6},{}",
+                    csv_field(&path),
+                    csv_field(&record.algorithm),
+                    digest,
+                    record.size.map(|size| size.to_string()).unwrap_or_default(),
+                    record.elapsed.as_secs_f64(),
+                    csv_field(&error)
+                )?;
+            }
+            _ => match &record.result {
+                Err(err) => writeln!(errors, "{}: {}", path, err)?,
+                Ok(digest) => match format {
+                    OutputFormat::Gnu => {
+                        let (escaped, name) = gnu_escape(&path);
+                        let prefix = if escaped { "\\" } else { "" };
+                        writeln!(out, "{}{}  {}", prefix, encoding.encode(digest), name)?
+                    }
+                    OutputFormat::Bsd => writeln!(
+                        out,
+                        "{} ({}) = {}",
+                        bsd_tag(&record.algorithm),
+                        path,
+                        encoding.encode(digest)
+                    )?,
+                    _ => writeln!(out, "{} {}", path, encoding.encode(digest))?,
+                },
+            },
+        }
+    }
+    if format == OutputFormat::Json {
+        writeln!(out, "]")?;
+    }
+    Ok(())
+}
+
+/// Format the records into a string, failures are kept in the output
+pub fn format_records(format: OutputFormat, encoding: Encoding, records: &[HashRecord]) -> String {
+    let mut out = Vec::new();
+    let mut errors = Vec::new();
+    write_records(format, encoding, records, &mut out, &mut errors).unwrap();
+    out.extend(errors);
+    String::from_utf8_lossy(&out).into_owned()
+}
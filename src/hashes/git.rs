@@ -0,0 +1,201 @@
+//! Git object IDs of files and directories, the same as `git hash-object` and
+//! `git write-tree` print, in the SHA-1 and SHA-256 object formats.
+//!
+//! An object ID is the digest of `<type> <length>\0` followed by the object. A tree
+//! object lists its entries as `<mode> <name>\0` and the raw ID of the entry, sorted
+//! by name with directory names compared as if they ended with `/`. As in git,
+//! directories without any file are not part of trees, symbolic links are blobs of
+//! the path they point to, and `.git` directories are left out. Nested repositories
+//! are hashed as plain directories, where git would record their commit.
+use super::hasher::{Checksum, PDHasher};
+use super::{Sha1, Sha256};
+use crate::utils::errors::{ArgumentError, ChecksumError, ReadError};
+use crate::utils::io::link_target;
+use crate::utils::progress_read::ProgressRead;
+use digest::Digest;
+use rayon::prelude::*;
+use std::ffi::OsStr;
+use std::fs::{self, DirEntry, Metadata};
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+const MODE_FILE: &str = "100644";
+const MODE_EXECUTABLE: &str = "100755";
+const MODE_LINK: &str = "120000";
+const MODE_TREE: &str = "40000";
+
+/// Hash function of the object IDs of a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectFormat {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl FromStr for ObjectFormat {
+    type Err = failure::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "sha1" => ObjectFormat::Sha1,
+            "sha256" => ObjectFormat::Sha256,
+            _ => Err(ArgumentError::UnknownObjectFormat { name: s.to_owned() })?,
+        })
+    }
+}
+
+impl ObjectFormat {
+    /// Hasher of an object of `kind` with `length` bytes, fed with its header
+    fn object_hasher(self, kind: &str, length: u64) -> Box<dyn PDHasher> {
+        let mut hasher: Box<dyn PDHasher> = match self {
+            ObjectFormat::Sha1 => Box::new(Sha1::new()),
+            ObjectFormat::Sha256 => Box::new(Sha256::new()),
+        };
+        hasher.update(format!("{} {}\0", kind, length).as_bytes());
+        hasher
+    }
+
+    /// ID of the object of `kind` holding `content`
+    pub fn object_id(self, kind: &str, content: &[u8]) -> Vec<u8> {
+        let mut hasher = self.object_hasher(kind, content.len() as u64);
+        hasher.update(content);
+        hasher.finish()
+    }
+
+    /// Blob ID of the whole content of `reader`
+    pub fn blob_id_from_reader<R: Read>(self, mut reader: R) -> std::io::Result<Vec<u8>> {
+        let mut content = vec![];
+        reader.read_to_end(&mut content)?;
+        Ok(self.object_id("blob", &content))
+    }
+
+    /// Blob ID of a file, block device or FIFO, see `io::open_range`
+    pub fn blob_id(self, path: &Path) -> Result<Vec<u8>, failure::Error> {
+        let mut input = ProgressRead::from_file_path(path, None, true)?;
+        match input.total_bytes() {
+            Some(length) => {
+                let mut hasher = self.object_hasher("blob", length);
+                input.for_each_chunk(|chunk| hasher.update(chunk))?;
+                Ok(hasher.finish())
+            }
+            // the header holds the length, FIFOs are read before hashing
+            None => Ok(self.blob_id_from_reader(input)?),
+        }
+    }
+
+    /// Tree ID of a directory, the empty tree when it holds no file
+    pub fn tree_id(self, dir: &Path) -> Result<Vec<u8>, failure::Error> {
+        Ok(match self.subtree_id(dir)? {
+            Some(id) => id,
+            None => self.object_id("tree", &[]),
+        })
+    }
+
+    /// Tree ID of a directory, None when it holds no file. Entries are hashed in parallel
+    fn subtree_id(self, dir: &Path) -> Result<Option<Vec<u8>>, failure::Error> {
+        let entries = fs::read_dir(dir)
+            .and_then(|entries| entries.collect::<std::io::Result<Vec<_>>>())
+            .map_err(|err| ReadError::ReadDirError {
+                err,
+                dir: dir.to_string_lossy().to_string(),
+            })?;
+        let mut entries = entries
+            .par_iter()
+            .map(|entry| self.tree_entry(entry))
+            .collect::<Result<Vec<_>, failure::Error>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        entries.sort_by_cached_key(TreeEntry::sort_key);
+        let mut content = vec![];
+        for entry in entries {
+            content.extend_from_slice(entry.mode.as_bytes());
+            content.push(b' ');
+            content.extend_from_slice(&entry.name);
+            content.push(0);
+            content.extend_from_slice(&entry.id);
+        }
+        Ok(Some(self.object_id("tree", &content)))
+    }
+
+    /// Entry of a directory in its tree, None for entries git leaves out
+    fn tree_entry(self, entry: &DirEntry) -> Result<Option<TreeEntry>, failure::Error> {
+        let name = entry.file_name();
+        if name == ".git" {
+            return Ok(None);
+        }
+        let path = entry.path();
+        let failed = |err: &dyn std::fmt::Display| ChecksumError::GitObjectFailed {
+            path: path.to_string_lossy().to_string(),
+            err: err.to_string(),
+        };
+        // links are never followed, their metadata is their own
+        let meta = entry.metadata().map_err(|err| failed(&err))?;
+        let (mode, id) = if meta.file_type().is_symlink() {
+            let target = link_target(&path).map_err(|err| failed(&err))?;
+            (MODE_LINK, self.object_id("blob", &target))
+        } else if meta.is_dir() {
+            match self.subtree_id(&path)? {
+                Some(id) => (MODE_TREE, id),
+                None => return Ok(None),
+            }
+        } else if meta.is_file() {
+            let id = self.blob_id(&path).map_err(|err| failed(&err))?;
+            (file_mode(&meta), id)
+        } else {
+            // git does not track devices, FIFOs and sockets
+            return Ok(None);
+        };
+        Ok(Some(TreeEntry {
+            mode,
+            name: name_bytes(&name),
+            id,
+        }))
+    }
+}
+
+struct TreeEntry {
+    mode: &'static str,
+    name: Vec<u8>,
+    id: Vec<u8>,
+}
+
+impl TreeEntry {
+    fn sort_key(&self) -> Vec<u8> {
+        let mut key = self.name.clone();
+        if self.mode == MODE_TREE {
+            key.push(b'/');
+        }
+        key
+    }
+}
+
+/// Git records whether the owner can execute a file, and nothing else of its mode
+#[cfg(unix)]
+fn file_mode(meta: &Metadata) -> &'static str {
+    use std::os::unix::fs::PermissionsExt;
+    if meta.permissions().mode() & 0o100 != 0 {
+        MODE_EXECUTABLE
+    } else {
+        MODE_FILE
+    }
+}
+
+#[cfg(not(unix))]
+fn file_mode(_meta: &Metadata) -> &'static str {
+    MODE_FILE
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    name.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
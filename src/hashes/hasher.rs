@@ -1,49 +1,132 @@
+use super::encoding::Encoding;
+use crate::utils::io::{self, ByteRange, Input};
+use crate::utils::progress_read::ProgressRead;
 use digest::Digest;
 use generic_array::GenericArray;
 use std::io::Read;
+use std::path::Path;
+
+/// A checksum algorithm fed with consecutive buffers,
+/// implemented for every `Digest` as well as the non-cryptographic checksums
+pub trait Checksum: Send {
+    type Output: AsRef<[u8]> + Send;
+    /// Consume buffer
+    fn update(&mut self, buf: &[u8]);
+    /// Output result
+    fn finish(self) -> Self::Output;
+    /// Length of the result in bytes
+    fn output_size(&self) -> usize;
+}
+
+impl<D: Digest + Default + Send> Checksum for D {
+    type Output = GenericArray<u8, D::OutputSize>;
+
+    fn update(&mut self, buf: &[u8]) {
+        self.input(buf)
+    }
+    fn finish(self) -> Self::Output {
+        self.result()
+    }
+    fn output_size(&self) -> usize {
+        D::output_size()
+    }
+}
+
+/// A checksum whose intermediate state can be saved and restored,
+/// so that hashing can stop and later continue from the same byte
+pub trait Resumable: Checksum {
+    /// Serialise the state after the bytes consumed so far
+    fn export_state(&self) -> Vec<u8>;
+    /// Replace the state with one produced by `export_state` on the same algorithm,
+    /// returns false when the bytes are not such a state
+    fn import_state(&mut self, state: &[u8]) -> bool;
+}
 
 pub trait Hasher {
     type OutputArray;
     /// Produce a hash result from a reader(stdin, file, ...)
     fn from_reader<R: Read>(self, reader: R) -> Result<Self::OutputArray, failure::Error>;
+    /// Produce a hash result from a file opened with `ProgressRead::from_file_path`
+    #[allow(clippy::wrong_self_convention)]
+    fn from_input(self, input: ProgressRead<Input>) -> Result<Self::OutputArray, failure::Error>
+    where
+        Self: Sized,
+    {
+        self.from_reader(input)
+    }
 }
 
-impl<D: Digest> Hasher for D {
-    type OutputArray = GenericArray<u8, D::OutputSize>;
+impl<C: Checksum> Hasher for C {
+    type OutputArray = C::Output;
 
     fn from_reader<R: Read>(mut self, mut reader: R) -> Result<Self::OutputArray, failure::Error> {
-        // Use a 10mb buffer
-        let mut buffer = vec![0; 10485760];
+        let mut buffer = io::buffer();
         loop {
             let read_bytes = reader.read(&mut buffer)?;
             if read_bytes == 0 {
                 break;
             } else {
-                self.input(&buffer[..read_bytes]);
+                self.update(&buffer[..read_bytes]);
             }
         }
-        Ok(self.result())
+        Ok(self.finish())
     }
+
+    fn from_input(
+        mut self,
+        mut input: ProgressRead<Input>,
+    ) -> Result<Self::OutputArray, failure::Error> {
+        input.for_each_chunk(|chunk| self.update(chunk))?;
+        Ok(self.finish())
+    }
+}
+
+/// Hash `range.length` bytes of the file from `range.offset`, without a progress bar
+pub fn hash_file_range<H: Hasher, P: AsRef<Path>>(
+    hasher: H,
+    path: P,
+    range: ByteRange,
+) -> Result<H::OutputArray, failure::Error> {
+    hasher.from_input(ProgressRead::from_file_range(path, range, None, true)?)
 }
 
-pub trait PDHasher {
+pub trait PDHasher: Send {
     /// Consume buffer
     fn consume(&mut self, buf: &[u8]);
+    /// Length of the result in bytes
+    fn digest_size(&self) -> usize;
+    /// output result bytes
+    fn digest(self: Box<Self>) -> Vec<u8>;
     /// output result
     fn produce(self: Box<Self>) -> Result<String, failure::Error>;
 }
 
-impl<D: Digest> PDHasher for D {
+impl<C: Checksum> PDHasher for C {
     fn consume(&mut self, buf: &[u8]) {
-        self.input(buf)
+        self.update(buf)
+    }
+    fn digest_size(&self) -> usize {
+        self.output_size()
+    }
+    fn digest(self: Box<Self>) -> Vec<u8> {
+        self.finish().as_ref().to_vec()
     }
     fn produce(self: Box<Self>) -> Result<String, failure::Error> {
-        use std::fmt::Write;
-        let hash = self.result();
-        let mut result = String::new();
-        for byte in hash.as_slice() {
-            write!(&mut result, "{:x}", byte)?;
-        }
-        Ok(result)
+        Ok(Encoding::Hex.encode(self.finish().as_ref()))
+    }
+}
+
+/// Hashers picked at runtime by name are checksums as well
+impl Checksum for Box<dyn PDHasher> {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, buf: &[u8]) {
+        (**self).consume(buf)
+    }
+    fn finish(self) -> Self::Output {
+        PDHasher::digest(self)
+    }
+    fn output_size(&self) -> usize {
+        (**self).digest_size()
     }
 }
@@ -0,0 +1,51 @@
+use super::hasher::Checksum;
+use digest::{BlockInput, FixedOutput, Input, Reset};
+use generic_array::typenum::Unsigned;
+use generic_array::{ArrayLength, GenericArray};
+use hmac::{Hmac, Mac};
+
+/// HMAC keyed hashing over any block based digest
+#[derive(Clone)]
+pub struct HmacChecksum<D>
+where
+    D: Input + BlockInput + FixedOutput + Reset + Default + Clone,
+    D::BlockSize: ArrayLength<u8>,
+{
+    mac: Hmac<D>,
+}
+
+impl<D> HmacChecksum<D>
+where
+    D: Input + BlockInput + FixedOutput + Reset + Default + Clone,
+    D::BlockSize: ArrayLength<u8>,
+{
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            // keys of any length are valid for HMAC
+            mac: Hmac::new_varkey(key).unwrap(),
+        }
+    }
+
+    /// Whether `code` is the HMAC of the input, compared in constant time
+    pub fn verify(self, code: &[u8]) -> bool {
+        self.mac.verify(code).is_ok()
+    }
+}
+
+impl<D> Checksum for HmacChecksum<D>
+where
+    D: Input + BlockInput + FixedOutput + Reset + Default + Clone + Send,
+    D::BlockSize: ArrayLength<u8>,
+{
+    type Output = GenericArray<u8, D::OutputSize>;
+
+    fn update(&mut self, buf: &[u8]) {
+        self.mac.input(buf);
+    }
+    fn finish(self) -> Self::Output {
+        self.mac.result().code()
+    }
+    fn output_size(&self) -> usize {
+        D::OutputSize::to_usize()
+    }
+}
@@ -1,6 +1,31 @@
+pub mod audit;
+pub mod blake;
+pub mod blocks;
+pub mod cache;
+pub mod checkpoint;
+pub mod database;
+pub mod dupes;
+pub mod encoding;
+pub mod fast;
+pub mod format;
+pub mod git;
 pub mod hasher;
+pub mod hmac;
+pub mod mtree;
+pub mod multi;
+pub mod registry;
+pub mod sha256_tree;
+pub mod sha2_state;
+pub mod stored;
+pub mod sums;
+pub mod tree;
+pub mod xof;
 
+pub use blake::Blake3;
+pub use blake2::Blake2b;
+pub use blake2::Blake2s;
 pub use digest;
+pub use fast::{Adler32, Crc32, Crc32c, Crc64, Xxh128, Xxh3};
 pub use md5::Md5;
 pub use sha1::Sha1;
 pub use sha2::Sha224;
@@ -0,0 +1,526 @@
+//! BSD mtree specifications, as read and written by `mtree(8)` and `bsdtar --format=mtree`.
+//!
+//! Specifications are written one entry per line with full paths (`./dir/file`),
+//! the form `mtree -C` prints. Both that form and the hierarchical one, where names
+//! are relative to the last directory entry and `..` goes back up, are read, along
+//! with `/set` and `/unset` defaults. Names are encoded with octal escapes as `vis(3)`
+//! does. Keywords other than those in `KEYWORDS` are ignored when verifying.
+use super::encoding::Encoding;
+use crate::utils::errors::{ArgumentError, ChecksumError};
+use crate::utils::io::{bytes_path, path_bytes};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, Metadata};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Keywords that can be written and verified
+pub const KEYWORDS: &[&str] = &[
+    "type",
+    "mode",
+    "uid",
+    "gid",
+    "size",
+    "time",
+    "link",
+    "nlink",
+    "md5digest",
+    "sha1digest",
+    "sha256digest",
+    "sha384digest",
+    "sha512digest",
+];
+
+/// Keywords written when none are given
+pub const DEFAULT_KEYWORDS: &str = "type,mode,uid,gid,size,time,link,sha256digest";
+
+/// Keywords of an entry by name
+pub type Keywords = BTreeMap<String, String>;
+
+/// Canonical name of a keyword, `mtree(8)` accepts short names for digests
+fn canonical_keyword(name: &str) -> &str {
+    match name {
+        "md5" => "md5digest",
+        "sha1" => "sha1digest",
+        "sha256" => "sha256digest",
+        "sha384" => "sha384digest",
+        "sha512" => "sha512digest",
+        _ => name,
+    }
+}
+
+/// Algorithm of a digest keyword, None for other keywords
+pub fn digest_algorithm(keyword: &str) -> Option<&'static str> {
+    match keyword {
+        "md5digest" => Some("md5"),
+        "sha1digest" => Some("sha1"),
+        "sha256digest" => Some("sha2-256"),
+        "sha384digest" => Some("sha2-384"),
+        "sha512digest" => Some("sha2-512"),
+        _ => None,
+    }
+}
+
+/// Check and canonicalise keywords given by name
+pub fn parse_keywords<S: AsRef<str>>(names: &[S]) -> Result<Vec<String>, failure::Error> {
+    names
+        .iter()
+        .map(|name| {
+            let keyword = canonical_keyword(name.as_ref());
+            if KEYWORDS.contains(&keyword) {
+                Ok(keyword.to_owned())
+            } else {
+                Err(ArgumentError::UnknownKeyword {
+                    name: name.as_ref().to_owned(),
+                    supported: KEYWORDS.join(", "),
+                }
+                .into())
+            }
+        })
+        .collect()
+}
+
+/// Encode a name with octal escapes for white space, `\`, `#`, glob characters
+/// and anything that is not printable ASCII
+pub fn encode_name(name: &[u8]) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for &byte in name {
+        match byte {
+            b'!'..=b'~' if !b"\\#*?[".contains(&byte) => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Undo `encode_name`, C escapes and `\\` are accepted as well
+pub fn decode_name(name: &str) -> Option<Vec<u8>> {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let escaped = *bytes.get(i + 1)?;
+        if escaped.is_ascii_digit() {
+            let digits = bytes.get(i + 1..i + 4)?;
+            let value = std::str::from_utf8(digits).ok()?;
+            decoded.push(u8::from_str_radix(value, 8).ok()?);
+            i += 4;
+            continue;
+        }
+        decoded.push(match escaped {
+            b's' => b' ',
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            other => other,
+        });
+        i += 2;
+    }
+    Some(decoded)
+}
+
+/// Name of a path relative to the root in a specification, `.` for the root
+pub fn spec_path(path: &Path) -> String {
+    if path.as_os_str().is_empty() {
+        ".".to_owned()
+    } else {
+        let mut name = b"./".to_vec();
+        name.extend(path_bytes(path));
+        encode_name(&name)
+    }
+}
+
+/// An entry of a tree, links are not followed
+#[derive(Debug)]
+pub struct Node {
+    /// Path relative to the root, empty for the root itself
+    pub path: PathBuf,
+    pub meta: Metadata,
+}
+
+impl Node {
+    pub fn is_file(&self) -> bool {
+        self.meta.file_type().is_file()
+    }
+}
+
+/// Every entry of the tree at `root` in pre-order, entries of a directory in name order
+pub fn scan(root: &Path) -> io::Result<Vec<Node>> {
+    let meta = fs::symlink_metadata(root)?;
+    let is_dir = meta.is_dir();
+    let mut nodes = vec![Node {
+        path: PathBuf::new(),
+        meta,
+    }];
+    if is_dir {
+        scan_dir(root, Path::new(""), &mut nodes)?;
+    }
+    Ok(nodes)
+}
+
+fn scan_dir(root: &Path, dir: &Path, nodes: &mut Vec<Node>) -> io::Result<()> {
+    let mut children = fs::read_dir(root.join(dir))?
+        .map(|entry| {
+            let entry = entry?;
+            Ok(Node {
+                path: dir.join(entry.file_name()),
+                meta: entry.metadata()?,
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    children.sort_by(|left, right| left.path.cmp(&right.path));
+    for child in children {
+        let path = child.path.clone();
+        let is_dir = child.meta.is_dir();
+        nodes.push(child);
+        if is_dir {
+            scan_dir(root, &path, nodes)?;
+        }
+    }
+    Ok(())
+}
+
+fn type_name(meta: &Metadata) -> &'static str {
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        return "dir";
+    } else if file_type.is_symlink() {
+        return "link";
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return "fifo";
+        } else if file_type.is_block_device() {
+            return "block";
+        } else if file_type.is_char_device() {
+            return "char";
+        } else if file_type.is_socket() {
+            return "socket";
+        }
+    }
+    "file"
+}
+
+#[cfg(unix)]
+fn owner_values(meta: &Metadata) -> (u32, u32, u32, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (meta.mode(), meta.uid(), meta.gid(), meta.nlink())
+}
+
+#[cfg(not(unix))]
+fn owner_values(meta: &Metadata) -> (u32, u32, u32, u64) {
+    let mode = if meta.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    };
+    (mode, 0, 0, 1)
+}
+
+fn format_time(nanos: i128) -> String {
+    format!(
+        "{}.{:09}",
+        nanos.div_euclid(1_000_000_000),
+        nanos.rem_euclid(1_000_000_000)
+    )
+}
+
+/// Value of a keyword for a node as written in specifications, None when it does
+/// not apply to the node. `root` is needed to read link targets, `digests` holds
+/// the digests of the node by algorithm
+pub fn keyword_value(
+    root: &Path,
+    node: &Node,
+    keyword: &str,
+    digests: &HashMap<String, Vec<u8>>,
+) -> Option<String> {
+    let (mode, uid, gid, nlink) = owner_values(&node.meta);
+    Some(match keyword {
+        "type" => type_name(&node.meta).to_owned(),
+        "mode" => format!("{:04o}", mode & 0o7777),
+        "uid" => uid.to_string(),
+        "gid" => gid.to_string(),
+        "nlink" => nlink.to_string(),
+        "size" if node.is_file() => node.meta.len().to_string(),
+        "time" => {
+            let modified = node.meta.modified().ok()?;
+            let nanos = match modified.duration_since(std::time::UNIX_EPOCH) {
+                Ok(since) => since.as_nanos() as i128,
+                Err(err) => -(err.duration().as_nanos() as i128),
+            };
+            format_time(nanos)
+        }
+        "link" if node.meta.file_type().is_symlink() => {
+            let target = fs::read_link(root.join(&node.path)).ok()?;
+            encode_name(&path_bytes(&target))
+        }
+        _ => {
+            let algorithm = digest_algorithm(keyword).filter(|_| node.is_file())?;
+            Encoding::Hex.encode(digests.get(algorithm)?)
+        }
+    })
+}
+
+/// Write a specification of `nodes` with `keywords`, `digests` of the files by path
+pub fn write_spec<W: Write>(
+    root: &Path,
+    nodes: &[Node],
+    keywords: &[String],
+    digests: &HashMap<PathBuf, HashMap<String, Vec<u8>>>,
+    out: &mut W,
+) -> io::Result<()> {
+    let no_digests = HashMap::new();
+    writeln!(out, "#mtree")?;
+    for node in nodes {
+        let node_digests = digests.get(&node.path).unwrap_or(&no_digests);
+        write!(out, "{}", spec_path(&node.path))?;
+        for keyword in keywords {
+            if let Some(value) = keyword_value(root, node, keyword, node_digests) {
+                write!(out, " {}={}", keyword, value)?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// An entry of a specification
+#[derive(Debug)]
+pub struct SpecEntry {
+    /// Path relative to the root, empty for the root itself
+    pub path: PathBuf,
+    /// Keywords with canonical names, including the `/set` defaults
+    pub keywords: Keywords,
+}
+
+impl SpecEntry {
+    /// Whether a missing file is not reported
+    pub fn is_optional(&self) -> bool {
+        self.keywords.contains_key("optional")
+    }
+
+    /// Whether what lies below this directory is not compared
+    pub fn ignores_children(&self) -> bool {
+        self.keywords.contains_key("ignore")
+    }
+}
+
+/// Value of a keyword of a specification as `keyword_value` would write it,
+/// None when it can not be read
+fn canonical_value(keyword: &str, value: &str) -> Option<String> {
+    Some(match keyword {
+        "mode" => format!("{:04o}", u32::from_str_radix(value, 8).ok()? & 0o7777),
+        "uid" | "gid" | "nlink" | "size" => value.parse::<u64>().ok()?.to_string(),
+        "time" => {
+            // the part after the dot counts nanoseconds, bsdtar writes it unpadded
+            let (seconds, nanos) = match value.find('.') {
+                Some(dot) => (&value[..dot], &value[dot + 1..]),
+                None => (value, "0"),
+            };
+            if !nanos.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            let nanos = nanos
+                .parse::<i128>()
+                .ok()
+                .filter(|nanos| *nanos < 1_000_000_000)?;
+            format_time(seconds.parse::<i128>().ok()? * 1_000_000_000 + nanos)
+        }
+        "link" => encode_name(&decode_name(value)?),
+        _ if digest_algorithm(keyword).is_some() => value.to_ascii_lowercase(),
+        _ => value.to_owned(),
+    })
+}
+
+fn split_keywords(words: &[&str], keywords: &mut Keywords) {
+    for word in words {
+        let (name, value) = match word.find('=') {
+            Some(equal) => (&word[..equal], &word[equal + 1..]),
+            None => (*word, ""),
+        };
+        keywords.insert(canonical_keyword(name).to_owned(), value.to_owned());
+    }
+}
+
+/// Read the entries of a specification in either form
+pub fn parse_spec<R: BufRead>(reader: R, file: &str) -> Result<Vec<SpecEntry>, failure::Error> {
+    let invalid = |line: usize| ChecksumError::InvalidSpec {
+        file: file.to_owned(),
+        line,
+    };
+    let mut entries = vec![];
+    let mut defaults = Keywords::new();
+    // directory of the relative names of the hierarchical form
+    let mut cwd = PathBuf::new();
+    let mut pending = String::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        // a trailing backslash continues the entry on the next line
+        if let Some(start) = line.strip_suffix('\\') {
+            pending.push_str(start);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(&line);
+        let line = std::mem::take(&mut pending);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (first, rest) = match words.split_first() {
+            Some((first, _)) if first.starts_with('#') => continue,
+            Some(split) => split,
+            None => continue,
+        };
+        match *first {
+            "/set" => split_keywords(rest, &mut defaults),
+            "/unset" => {
+                for name in rest {
+                    if *name == "all" {
+                        defaults.clear();
+                    } else {
+                        defaults.remove(canonical_keyword(name));
+                    }
+                }
+            }
+            ".." => {
+                cwd.pop();
+            }
+            name => {
+                let mut keywords = defaults.clone();
+                split_keywords(rest, &mut keywords);
+                let name = bytes_path(decode_name(name).ok_or_else(|| invalid(number + 1))?);
+                let full = name.components().count() > 1;
+                let relative: PathBuf = name
+                    .components()
+                    .filter(|component| component.as_os_str() != ".")
+                    .collect();
+                let path = if full || cwd.as_os_str().is_empty() {
+                    relative
+                } else if relative.as_os_str().is_empty() {
+                    cwd.clone()
+                } else {
+                    cwd.join(relative)
+                };
+                if !full && keywords.get("type").map(String::as_str) == Some("dir") {
+                    cwd = path.clone();
+                }
+                entries.push(SpecEntry { path, keywords });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Keywords of a specification entry whose values differ from those of `node`
+pub fn differences(
+    root: &Path,
+    entry: &SpecEntry,
+    node: &Node,
+    digests: &HashMap<String, Vec<u8>>,
+) -> Vec<String> {
+    entry
+        .keywords
+        .iter()
+        .filter(|(keyword, _)| KEYWORDS.contains(&keyword.as_str()))
+        .filter(|(keyword, value)| {
+            canonical_value(keyword, value) != keyword_value(root, node, keyword, digests)
+        })
+        .map(|(keyword, _)| keyword.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(spec: &str) -> Vec<SpecEntry> {
+        parse_spec(spec.as_bytes(), "spec").unwrap()
+    }
+
+    fn paths(entries: &[SpecEntry]) -> Vec<&Path> {
+        entries.iter().map(|entry| entry.path.as_path()).collect()
+    }
+
+    #[test]
+    fn names_round_trip_through_octal_escapes() {
+        let name = b"a b#\\*\xff";
+        let encoded = encode_name(name);
+        assert_eq!(encoded, "a\\040b\\043\\134\\052\\377");
+        assert_eq!(decode_name(&encoded).unwrap(), name);
+        assert_eq!(decode_name("a\\sb\\tc").unwrap(), b"a b\tc");
+        assert_eq!(decode_name("cut\\"), None);
+        assert_eq!(decode_name("cut\\01"), None);
+    }
+
+    #[test]
+    fn canonical_value_pads_unpadded_nanoseconds() {
+        // bsdtar writes the nanoseconds without leading zeros
+        assert_eq!(
+            canonical_value("time", "1704164645.5000").as_deref(),
+            Some("1704164645.000005000")
+        );
+        assert_eq!(
+            canonical_value("time", "1704164645").as_deref(),
+            Some("1704164645.000000000")
+        );
+        assert_eq!(canonical_value("time", "1.1000000000"), None);
+        assert_eq!(canonical_value("time", "1.-5"), None);
+    }
+
+    #[test]
+    fn canonical_value_normalises_numbers_and_digests() {
+        assert_eq!(canonical_value("mode", "100755").as_deref(), Some("0755"));
+        assert_eq!(canonical_value("size", "007").as_deref(), Some("7"));
+        assert_eq!(
+            canonical_value("sha256digest", "ABC").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(canonical_value("mode", "9"), None);
+    }
+
+    #[test]
+    fn parse_spec_applies_set_and_unset() {
+        let entries = parse(
+            "#mtree\n/set type=file uid=0 mode=0644\n./a size=1\n/unset uid\n./b mode=0600 \\\n    size=2\n",
+        );
+        assert_eq!(paths(&entries), [Path::new("a"), Path::new("b")]);
+        let (a, b) = (&entries[0].keywords, &entries[1].keywords);
+        assert_eq!(a.get("uid").map(String::as_str), Some("0"));
+        assert_eq!(a.get("mode").map(String::as_str), Some("0644"));
+        assert_eq!(b.get("uid"), None);
+        assert_eq!(b.get("mode").map(String::as_str), Some("0600"));
+        assert_eq!(b.get("size").map(String::as_str), Some("2"));
+    }
+
+    #[test]
+    fn parse_spec_follows_hierarchical_names() {
+        let entries = parse(
+            ". type=dir\ndir type=dir\nfile type=file\nsub type=dir\ndeep type=file sha256=AB\n..\n..\ntop type=file\n",
+        );
+        assert_eq!(
+            paths(&entries),
+            [
+                Path::new(""),
+                Path::new("dir"),
+                Path::new("dir/file"),
+                Path::new("dir/sub"),
+                Path::new("dir/sub/deep"),
+                Path::new("top"),
+            ]
+        );
+        assert!(entries[4].keywords.contains_key("sha256digest"));
+    }
+
+    #[test]
+    fn parse_spec_reports_the_line_of_a_bad_name() {
+        let err = parse_spec("./a type=file\n./b\\ type=file\n".as_bytes(), "spec").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChecksumError>(),
+            Some(ChecksumError::InvalidSpec { line: 2, .. })
+        ));
+    }
+}
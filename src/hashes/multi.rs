@@ -0,0 +1,126 @@
+use super::hasher::{Checksum, Hasher, PDHasher};
+use super::registry::{new_hasher, new_hmac, ALGORITHMS};
+use crate::utils::errors::ChecksumError;
+use crate::utils::io::{self, Input};
+use crate::utils::progress_read::ProgressRead;
+use std::io::Read;
+
+/// Feed a single read of the input into several hashers
+pub struct Hashes {
+    names: Vec<String>,
+    hashers: Vec<Box<dyn PDHasher>>,
+}
+
+impl Hashes {
+    /// Create the hashers, computed as HMAC when a key is given
+    pub fn new<S: AsRef<str>>(names: &[S], key: Option<&[u8]>) -> Result<Self, failure::Error> {
+        let mut hashes = Self {
+            names: Vec::with_capacity(names.len()),
+            hashers: Vec::with_capacity(names.len()),
+        };
+        for name in names {
+            let name = name.as_ref();
+            let hasher = new_hasher(name).ok_or_else(|| ChecksumError::UnknownAlgorithm {
+                name: name.to_owned(),
+                supported: ALGORITHMS.join(", "),
+            })?;
+            match key {
+                Some(key) => {
+                    let hasher =
+                        new_hmac(name, key).ok_or_else(|| ChecksumError::HmacUnsupported {
+                            name: name.to_owned(),
+                        })?;
+                    hashes.names.push(format!("hmac-{}", name));
+                    hashes.hashers.push(hasher);
+                }
+                None => {
+                    hashes.names.push(name.to_owned());
+                    hashes.hashers.push(hasher);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    pub fn consume(&mut self, buf: &[u8]) {
+        self.hashers
+            .iter_mut()
+            .for_each(|hasher| hasher.consume(buf));
+    }
+
+    /// Length of the digest of every algorithm, in order
+    pub fn output_sizes(&self) -> Vec<usize> {
+        self.hashers
+            .iter()
+            .map(|hasher| hasher.digest_size())
+            .collect()
+    }
+
+    /// Output every digest along with the algorithm name
+    pub fn digests(self) -> Vec<(String, Vec<u8>)> {
+        self.names
+            .into_iter()
+            .zip(self.hashers)
+            .map(|(name, hasher)| (name, hasher.digest()))
+            .collect()
+    }
+}
+
+/// Several algorithms as a single checksum, whose output is every digest one
+/// after the other to be split again with `Hashes::output_sizes`
+pub struct Concatenated(pub Hashes);
+
+impl Checksum for Concatenated {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, buf: &[u8]) {
+        self.0.consume(buf)
+    }
+    fn finish(self) -> Self::Output {
+        self.0
+            .hashers
+            .into_iter()
+            .flat_map(|hasher| hasher.digest())
+            .collect()
+    }
+    fn output_size(&self) -> usize {
+        self.0.output_sizes().iter().sum()
+    }
+}
+
+/// Cut the output of `Concatenated` into the digests of `sizes` bytes
+pub fn split_digests(sizes: &[usize], mut digests: &[u8]) -> Vec<Vec<u8>> {
+    sizes
+        .iter()
+        .map(|size| {
+            let (digest, rest) = digests.split_at(*size);
+            digests = rest;
+            digest.to_vec()
+        })
+        .collect()
+}
+
+impl Hasher for Hashes {
+    type OutputArray = Vec<(String, Vec<u8>)>;
+
+    fn from_reader<R: Read>(mut self, mut reader: R) -> Result<Self::OutputArray, failure::Error> {
+        let mut buffer = io::buffer();
+        loop {
+            let read_bytes = reader.read(&mut buffer)?;
+            if read_bytes == 0 {
+                break;
+            } else {
+                self.consume(&buffer[..read_bytes]);
+            }
+        }
+        Ok(self.digests())
+    }
+
+    fn from_input(
+        mut self,
+        mut input: ProgressRead<Input>,
+    ) -> Result<Self::OutputArray, failure::Error> {
+        input.for_each_chunk(|chunk| self.consume(chunk))?;
+        Ok(self.digests())
+    }
+}
@@ -0,0 +1,85 @@
+use super::hasher::PDHasher;
+use super::hmac::HmacChecksum;
+use super::sha256_tree::Sha256Tree;
+use super::xof::{Xof, SHAKE128_DEFAULT_LENGTH, SHAKE256_DEFAULT_LENGTH};
+use super::*;
+use digest::Digest;
+
+/// Names of all algorithms that can be created with `new_hasher`
+pub const ALGORITHMS: &[&str] = &[
+    "md5",
+    "sha1",
+    "sha2-224",
+    "sha2-256",
+    "sha2-384",
+    "sha2-512",
+    "sha3-224",
+    "sha3-256",
+    "sha3-384",
+    "sha3-512",
+    "blake2b",
+    "blake2s",
+    "blake3",
+    "crc32",
+    "crc32c",
+    "crc64",
+    "xxh3",
+    "xxh128",
+    "adler32",
+    "shake128",
+    "shake256",
+    "sha2-256-tree",
+];
+
+/// Create a hasher by its algorithm name,
+/// the short SHA-2 names (`sha256`, ...) are accepted as aliases
+pub fn new_hasher(name: &str) -> Option<Box<dyn PDHasher>> {
+    let hasher: Box<dyn PDHasher> = match name {
+        "md5" => Box::new(Md5::new()),
+        "sha1" => Box::new(Sha1::new()),
+        "sha2-224" | "sha224" => Box::new(Sha224::new()),
+        "sha2-256" | "sha256" => Box::new(Sha256::new()),
+        "sha2-384" | "sha384" => Box::new(Sha384::new()),
+        "sha2-512" | "sha512" => Box::new(Sha512::new()),
+        "sha3-224" => Box::new(Sha3_224::new()),
+        "sha3-256" => Box::new(Sha3_256::new()),
+        "sha3-384" => Box::new(Sha3_384::new()),
+        "sha3-512" => Box::new(Sha3_512::new()),
+        "blake2b" => Box::new(Blake2b::new()),
+        "blake2s" => Box::new(Blake2s::new()),
+        "blake3" => Box::new(Blake3::new()),
+        "crc32" => Box::new(Crc32::default()),
+        "crc32c" => Box::new(Crc32c::default()),
+        "crc64" => Box::new(Crc64::default()),
+        "xxh3" => Box::new(Xxh3::default()),
+        "xxh128" => Box::new(Xxh128::default()),
+        "adler32" => Box::new(Adler32::default()),
+        "shake128" => Box::new(Xof::shake128(SHAKE128_DEFAULT_LENGTH)),
+        "shake256" => Box::new(Xof::shake256(SHAKE256_DEFAULT_LENGTH)),
+        "sha2-256-tree" => Box::new(Sha256Tree::default()),
+        _ => return None,
+    };
+    Some(hasher)
+}
+
+/// Create an HMAC hasher keyed with `key` by its digest name,
+/// returns None for unknown names and for checksums that are not digests
+pub fn new_hmac(name: &str, key: &[u8]) -> Option<Box<dyn PDHasher>> {
+    let hasher: Box<dyn PDHasher> = match name {
+        "md5" => Box::new(HmacChecksum::<Md5>::new(key)),
+        "sha1" => Box::new(HmacChecksum::<Sha1>::new(key)),
+        "sha2-224" | "sha224" => Box::new(HmacChecksum::<Sha224>::new(key)),
+        "sha2-256" | "sha256" => Box::new(HmacChecksum::<Sha256>::new(key)),
+        "sha2-384" | "sha384" => Box::new(HmacChecksum::<Sha384>::new(key)),
+        "sha2-512" | "sha512" => Box::new(HmacChecksum::<Sha512>::new(key)),
+        "sha3-224" => Box::new(HmacChecksum::<Sha3_224>::new(key)),
+        "sha3-256" => Box::new(HmacChecksum::<Sha3_256>::new(key)),
+        "sha3-384" => Box::new(HmacChecksum::<Sha3_384>::new(key)),
+        "sha3-512" => Box::new(HmacChecksum::<Sha3_512>::new(key)),
+        "blake2b" => Box::new(HmacChecksum::<Blake2b>::new(key)),
+        "blake2s" => Box::new(HmacChecksum::<Blake2s>::new(key)),
+        "blake3" => Box::new(HmacChecksum::<Blake3>::new(key)),
+        _ => return None,
+    };
+    Some(hasher)
+}
@@ -0,0 +1,162 @@
+//! Chunked SHA-256 tree mode, whose digest can be computed by several workers at once.
+//!
+//! The input is cut into 1 MiB leaves, the last one possibly shorter and an empty input
+//! being a single empty leaf. A leaf digest is `SHA-256(0x00 || leaf)`, leaves are then
+//! combined level by level as `SHA-256(0x01 || left || right)`, an odd node at the end
+//! of a level moving up unchanged. The root is the digest; it is not the SHA-256 of
+//! the file.
+use super::hasher::Checksum;
+use crate::utils::io::ByteRange;
+use crate::utils::progress_read::{style_bar, style_spinner, ProgressRead};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Bytes in a leaf
+pub const LEAF_SIZE: u64 = 1024 * 1024;
+/// Leaves read by one worker in a row when a file is split
+const LEAVES_PER_PART: u64 = 16;
+
+fn leaf_hasher() -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.input([0u8]);
+    hasher
+}
+
+fn node_digest(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.input([1u8]);
+    hasher.input(left);
+    hasher.input(right);
+    hasher.result().to_vec()
+}
+
+/// Combine leaf digests up to the root
+fn root_digest(mut level: Vec<Vec<u8>>) -> Vec<u8> {
+    if level.is_empty() {
+        return leaf_hasher().result().to_vec();
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_digest(left, right),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level.pop().unwrap()
+}
+
+/// Streaming form of the tree mode, collecting leaf digests as the input goes
+pub struct Sha256Tree {
+    leaf: Sha256,
+    /// Bytes in the current leaf
+    filled: u64,
+    leaves: Vec<Vec<u8>>,
+}
+
+impl Default for Sha256Tree {
+    fn default() -> Self {
+        Self {
+            leaf: leaf_hasher(),
+            filled: 0,
+            leaves: vec![],
+        }
+    }
+}
+
+impl Sha256Tree {
+    /// Digests of every leaf, including the last partial one
+    fn into_leaves(mut self) -> Vec<Vec<u8>> {
+        if self.filled > 0 {
+            self.leaves.push(self.leaf.result().to_vec());
+        }
+        self.leaves
+    }
+}
+
+impl Checksum for Sha256Tree {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            let take = buf.len().min((LEAF_SIZE - self.filled) as usize);
+            self.leaf.input(&buf[..take]);
+            self.filled += take as u64;
+            buf = &buf[take..];
+            if self.filled == LEAF_SIZE {
+                let leaf = std::mem::replace(&mut self.leaf, leaf_hasher());
+                self.leaves.push(leaf.result().to_vec());
+                self.filled = 0;
+            }
+        }
+    }
+    fn finish(self) -> Self::Output {
+        root_digest(self.into_leaves())
+    }
+    fn output_size(&self) -> usize {
+        32
+    }
+}
+
+/// Hash the range of a file with the rayon workers reading disjoint parts,
+/// progress of every part goes to the single bar `pb`
+pub fn hash_file(
+    path: &Path,
+    range: ByteRange,
+    pb: Option<ProgressBar>,
+    silent: bool,
+) -> Result<Vec<u8>, failure::Error> {
+    // opening the whole range checks the file and gives the length to split
+    let mut input = ProgressRead::from_file_part(path, range, pb.clone(), silent)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let length = match input.total_bytes() {
+        Some(length) => length,
+        None => {
+            // a FIFO can only be read once, from start to end
+            if let Some(bar) = &pb {
+                style_spinner(bar, &name);
+            }
+            let mut tree = Sha256Tree::default();
+            let result = input.for_each_chunk(|chunk| tree.update(chunk));
+            if let Some(bar) = &pb {
+                match result {
+                    Ok(_) => bar.finish_with_message("Done."),
+                    Err(_) => bar.finish_and_clear(),
+                }
+            }
+            result?;
+            return Ok(tree.finish());
+        }
+    };
+    drop(input);
+    if let Some(bar) = &pb {
+        style_bar(bar, &name, length);
+    }
+    let part_size = LEAF_SIZE * LEAVES_PER_PART;
+    let parts = length.div_ceil(part_size);
+    let leaves = (0..parts)
+        .into_par_iter()
+        .map(|part| {
+            let offset = part * part_size;
+            let part_range = ByteRange {
+                offset: range.offset + offset,
+                length: Some(part_size.min(length - offset)),
+            };
+            let mut input = ProgressRead::from_file_part(path, part_range, pb.clone(), silent)?;
+            let mut tree = Sha256Tree::default();
+            input.for_each_chunk(|chunk| tree.update(chunk))?;
+            Ok(tree.into_leaves())
+        })
+        .collect::<Result<Vec<_>, failure::Error>>();
+    if let Some(bar) = &pb {
+        match leaves {
+            Ok(_) => bar.finish_with_message("Done."),
+            Err(_) => bar.finish_and_clear(),
+        }
+    }
+    Ok(root_digest(leaves?.into_iter().flatten().collect()))
+}
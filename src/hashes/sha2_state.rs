@@ -0,0 +1,241 @@
+//! SHA-2 with an exportable state, built on the compression functions of the `sha2` crate.
+//! Digests are the same as `sha2::Sha256` and friends, the state can be saved between
+//! any two updates to resume hashing later.
+use super::hasher::{Checksum, Resumable};
+use sha2::{compress256, compress512};
+use std::convert::TryInto;
+
+const SHA224_INIT: [u32; 8] = [
+    0xc105_9ed8,
+    0x367c_d507,
+    0x3070_dd17,
+    0xf70e_5939,
+    0xffc0_0b31,
+    0x6858_1511,
+    0x64f9_8fa7,
+    0xbefa_4fa4,
+];
+const SHA256_INIT: [u32; 8] = [
+    0x6a09_e667,
+    0xbb67_ae85,
+    0x3c6e_f372,
+    0xa54f_f53a,
+    0x510e_527f,
+    0x9b05_688c,
+    0x1f83_d9ab,
+    0x5be0_cd19,
+];
+const SHA384_INIT: [u64; 8] = [
+    0xcbbb_9d5d_c105_9ed8,
+    0x629a_292a_367c_d507,
+    0x9159_015a_3070_dd17,
+    0x152f_ecd8_f70e_5939,
+    0x6733_2667_ffc0_0b31,
+    0x8eb4_4a87_6858_1511,
+    0xdb0c_2e0d_64f9_8fa7,
+    0x47b5_481d_befa_4fa4,
+];
+const SHA512_INIT: [u64; 8] = [
+    0x6a09_e667_f3bc_c908,
+    0xbb67_ae85_84ca_a73b,
+    0x3c6e_f372_fe94_f82b,
+    0xa54f_f53a_5f1d_36f1,
+    0x510e_527f_ade6_82d1,
+    0x9b05_688c_2b3e_6c1f,
+    0x1f83_d9ab_fb41_bd6b,
+    0x5be0_cd19_137e_2179,
+];
+
+/// SHA-224 and SHA-256, which differ in initial values and output length
+#[derive(Clone)]
+pub struct Sha256State {
+    h: [u32; 8],
+    block: [u8; 64],
+    /// Bytes waiting in `block`
+    filled: usize,
+    /// Bytes consumed so far
+    length: u64,
+    output_len: usize,
+}
+
+impl Sha256State {
+    fn new(h: [u32; 8], output_len: usize) -> Self {
+        Self {
+            h,
+            block: [0; 64],
+            filled: 0,
+            length: 0,
+            output_len,
+        }
+    }
+    pub fn sha224() -> Self {
+        Self::new(SHA224_INIT, 28)
+    }
+    pub fn sha256() -> Self {
+        Self::new(SHA256_INIT, 32)
+    }
+}
+
+impl Checksum for Sha256State {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, mut buf: &[u8]) {
+        self.length += buf.len() as u64;
+        if self.filled > 0 {
+            let take = buf.len().min(64 - self.filled);
+            self.block[self.filled..self.filled + take].copy_from_slice(&buf[..take]);
+            self.filled += take;
+            buf = &buf[take..];
+            if self.filled < 64 {
+                return;
+            }
+            compress256(&mut self.h, &self.block);
+            self.filled = 0;
+        }
+        let mut blocks = buf.chunks_exact(64);
+        for block in &mut blocks {
+            compress256(&mut self.h, block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.block[..rest.len()].copy_from_slice(rest);
+        self.filled = rest.len();
+    }
+    fn finish(mut self) -> Self::Output {
+        let bit_length = self.length.wrapping_mul(8);
+        // 0x80, zeros up to 56 bytes into the block, then the length
+        let mut padding = vec![0x80];
+        padding.resize(1 + (119 - self.filled) % 64, 0);
+        padding.extend_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+        let mut output: Vec<u8> = self.h.iter().flat_map(|word| word.to_be_bytes()).collect();
+        output.truncate(self.output_len);
+        output
+    }
+    fn output_size(&self) -> usize {
+        self.output_len
+    }
+}
+
+impl Resumable for Sha256State {
+    /// Chaining values, byte count, then the pending bytes of the block
+    fn export_state(&self) -> Vec<u8> {
+        let mut state: Vec<u8> = self.h.iter().flat_map(|word| word.to_be_bytes()).collect();
+        state.extend_from_slice(&self.length.to_be_bytes());
+        state.extend_from_slice(&self.block[..self.filled]);
+        state
+    }
+    fn import_state(&mut self, state: &[u8]) -> bool {
+        if state.len() < 40 {
+            return false;
+        }
+        let length = u64::from_be_bytes(state[32..40].try_into().unwrap());
+        let pending = &state[40..];
+        if pending.len() as u64 != length % 64 {
+            return false;
+        }
+        for (word, bytes) in self.h.iter_mut().zip(state[..32].chunks_exact(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        self.length = length;
+        self.block[..pending.len()].copy_from_slice(pending);
+        self.filled = pending.len();
+        true
+    }
+}
+
+/// SHA-384 and SHA-512, which differ in initial values and output length
+#[derive(Clone)]
+pub struct Sha512State {
+    h: [u64; 8],
+    block: [u8; 128],
+    /// Bytes waiting in `block`
+    filled: usize,
+    /// Bytes consumed so far
+    length: u128,
+    output_len: usize,
+}
+
+impl Sha512State {
+    fn new(h: [u64; 8], output_len: usize) -> Self {
+        Self {
+            h,
+            block: [0; 128],
+            filled: 0,
+            length: 0,
+            output_len,
+        }
+    }
+    pub fn sha384() -> Self {
+        Self::new(SHA384_INIT, 48)
+    }
+    pub fn sha512() -> Self {
+        Self::new(SHA512_INIT, 64)
+    }
+}
+
+impl Checksum for Sha512State {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, mut buf: &[u8]) {
+        self.length += buf.len() as u128;
+        if self.filled > 0 {
+            let take = buf.len().min(128 - self.filled);
+            self.block[self.filled..self.filled + take].copy_from_slice(&buf[..take]);
+            self.filled += take;
+            buf = &buf[take..];
+            if self.filled < 128 {
+                return;
+            }
+            compress512(&mut self.h, &self.block);
+            self.filled = 0;
+        }
+        let mut blocks = buf.chunks_exact(128);
+        for block in &mut blocks {
+            compress512(&mut self.h, block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.block[..rest.len()].copy_from_slice(rest);
+        self.filled = rest.len();
+    }
+    fn finish(mut self) -> Self::Output {
+        let bit_length = self.length.wrapping_mul(8);
+        // 0x80, zeros up to 112 bytes into the block, then the length
+        let mut padding = vec![0x80];
+        padding.resize(1 + (239 - self.filled) % 128, 0);
+        padding.extend_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+        let mut output: Vec<u8> = self.h.iter().flat_map(|word| word.to_be_bytes()).collect();
+        output.truncate(self.output_len);
+        output
+    }
+    fn output_size(&self) -> usize {
+        self.output_len
+    }
+}
+
+impl Resumable for Sha512State {
+    /// Chaining values, byte count, then the pending bytes of the block
+    fn export_state(&self) -> Vec<u8> {
+        let mut state: Vec<u8> = self.h.iter().flat_map(|word| word.to_be_bytes()).collect();
+        state.extend_from_slice(&self.length.to_be_bytes());
+        state.extend_from_slice(&self.block[..self.filled]);
+        state
+    }
+    fn import_state(&mut self, state: &[u8]) -> bool {
+        if state.len() < 80 {
+            return false;
+        }
+        let length = u128::from_be_bytes(state[64..80].try_into().unwrap());
+        let pending = &state[80..];
+        if pending.len() as u128 != length % 128 {
+            return false;
+        }
+        for (word, bytes) in self.h.iter_mut().zip(state[..64].chunks_exact(8)) {
+            *word = u64::from_be_bytes(bytes.try_into().unwrap());
+        }
+        self.length = length;
+        self.block[..pending.len()].copy_from_slice(pending);
+        self.filled = pending.len();
+        true
+    }
+}
@@ -0,0 +1,298 @@
+//! Checksums stored along with the files they belong to, for bit-rot detection.
+//!
+//! A sidecar file `<file>.<algorithm>` holds a line in the `sha256sum` format and is
+//! given the modification time of the file it was written for. The extended attribute
+//! `user.checksum.<algorithm>` holds the hex digest, and `user.checksum.<algorithm>.mtime`
+//! the modification time in nanoseconds. A stored digest that no longer matches while
+//! the modification time is the recorded one means the content changed without being
+//! written to, which is what scrubbing looks for.
+use super::cache::FileStamp;
+use super::encoding::Encoding;
+use super::format::gnu_escape_bytes;
+use super::sums;
+use crate::utils::io::{bytes_path, path_bytes};
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where checksums are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumStore {
+    /// A checksum file next to each file
+    Sidecar,
+    /// Extended attributes of each file
+    Xattr,
+}
+
+/// Largest file taken for a sidecar
+const MAX_SIDECAR_SIZE: u64 = 64 * 1024;
+
+/// A digest read from a store, with the modification time of the file it was computed on
+#[derive(Debug)]
+pub struct StoredChecksum {
+    pub digest: Vec<u8>,
+    /// Nanoseconds since the Unix epoch
+    pub mtime: i128,
+}
+
+/// Name of an algorithm in sidecar extensions and attribute names,
+/// the one `sha256sum` and friends are known by for SHA-2
+pub fn short_name(algorithm: &str) -> &str {
+    match algorithm {
+        "sha2-224" => "sha224",
+        "sha2-256" => "sha256",
+        "sha2-384" => "sha384",
+        "sha2-512" => "sha512",
+        _ => algorithm,
+    }
+}
+
+pub fn sidecar_path(file: &Path, algorithm: &str) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".");
+    path.push(short_name(algorithm));
+    PathBuf::from(path)
+}
+
+/// The file a sidecar path belongs to, None when `path` is not named as a sidecar
+pub fn sidecar_target(path: &Path, algorithm: &str) -> Option<PathBuf> {
+    let name = path_bytes(Path::new(path.file_name()?));
+    let stem = name
+        .strip_suffix(short_name(algorithm).as_bytes())?
+        .strip_suffix(b".")?;
+    if stem.is_empty() {
+        return None;
+    }
+    Some(path.with_file_name(bytes_path(stem.to_vec())))
+}
+
+/// Lines of a sidecar that are not blank, file names in it need not be UTF-8
+fn sidecar_lines(content: &[u8]) -> impl Iterator<Item = &[u8]> {
+    content
+        .split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+}
+
+/// Whether `path` is a sidecar: it is named as one and holds a single checksum line
+/// for the file it is named after. Other files with such a name are user data
+pub fn is_sidecar(path: &Path, algorithm: &str) -> bool {
+    let target = match sidecar_target(path, algorithm) {
+        Some(target) => target,
+        None => return false,
+    };
+    // a sidecar line is a digest and a file name, anything much larger is not one
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_file() && meta.len() <= MAX_SIDECAR_SIZE => {}
+        _ => return false,
+    }
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(_) => return false,
+    };
+    let mut lines = sidecar_lines(&content);
+    match (lines.next(), lines.next()) {
+        (Some(line), None) => match sums::parse_line_bytes(line, Encoding::Hex) {
+            Some(entry) => Some(entry.path.as_os_str()) == target.file_name(),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn attribute_name(algorithm: &str) -> String {
+    format!("user.checksum.{}", short_name(algorithm))
+}
+
+fn system_time(nanos: i128) -> Option<SystemTime> {
+    let nanos = u64::try_from(nanos).ok()?;
+    Some(UNIX_EPOCH + Duration::from_nanos(nanos))
+}
+
+impl ChecksumStore {
+    /// Store the digest of `file` computed while it had `stamp`
+    pub fn write(
+        self,
+        file: &Path,
+        algorithm: &str,
+        digest: &[u8],
+        stamp: &FileStamp,
+    ) -> io::Result<()> {
+        let hex = Encoding::Hex.encode(digest);
+        match self {
+            ChecksumStore::Sidecar => {
+                let name = path_bytes(Path::new(file.file_name().unwrap_or_default()));
+                let (escaped, name) = gnu_escape_bytes(&name);
+                let prefix = if escaped { "\\" } else { "" };
+                let sidecar = sidecar_path(file, algorithm);
+                let mut temp_name = sidecar.as_os_str().to_owned();
+                temp_name.push(".tmp");
+                let temp_path = PathBuf::from(temp_name);
+                let mut temp = File::create(&temp_path)?;
+                write!(temp, "{}{}  ", prefix, hex)?;
+                temp.write_all(&name)?;
+                temp.write_all(b"\n")?;
+                // the sidecar tells the time the file had when it was hashed
+                if let Some(mtime) = system_time(stamp.mtime) {
+                    temp.set_modified(mtime)?;
+                }
+                temp.sync_all()?;
+                fs::rename(&temp_path, &sidecar)
+            }
+            ChecksumStore::Xattr => {
+                let name = attribute_name(algorithm);
+                set_attribute(file, &name, hex.as_bytes())?;
+                set_attribute(
+                    file,
+                    &format!("{}.mtime", name),
+                    stamp.mtime.to_string().as_bytes(),
+                )
+            }
+        }
+    }
+
+    /// The digest stored for `file`, None when there is none
+    pub fn read(self, file: &Path, algorithm: &str) -> io::Result<Option<StoredChecksum>> {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_owned());
+        match self {
+            ChecksumStore::Sidecar => {
+                let sidecar = sidecar_path(file, algorithm);
+                let content = match fs::read(&sidecar) {
+                    Ok(content) => content,
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+                    Err(err) => return Err(err),
+                };
+                let entry = sidecar_lines(&content)
+                    .next()
+                    .and_then(|line| sums::parse_line_bytes(line, Encoding::Hex))
+                    .ok_or_else(|| invalid("malformed sidecar checksum file"))?;
+                Ok(Some(StoredChecksum {
+                    digest: entry.digest,
+                    mtime: FileStamp::of(&sidecar)?.mtime,
+                }))
+            }
+            ChecksumStore::Xattr => {
+                let name = attribute_name(algorithm);
+                let digest = match get_attribute(file, &name)? {
+                    Some(digest) => digest,
+                    None => return Ok(None),
+                };
+                let digest = String::from_utf8(digest)
+                    .ok()
+                    .and_then(|digest| Encoding::Hex.decode(&digest))
+                    .ok_or_else(|| invalid("malformed checksum attribute"))?;
+                let mtime = get_attribute(file, &format!("{}.mtime", name))?
+                    .and_then(|mtime| String::from_utf8(mtime).ok())
+                    .and_then(|mtime| mtime.parse().ok())
+                    .ok_or_else(|| invalid("malformed checksum time attribute"))?;
+                Ok(Some(StoredChecksum { digest, mtime }))
+            }
+        }
+    }
+}
+
+/// Attributes are set on the file a link points to, links themselves can not have any
+#[cfg(unix)]
+fn set_attribute(file: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    xattr::set(fs::canonicalize(file)?, name, value)
+}
+
+#[cfg(unix)]
+fn get_attribute(file: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    xattr::get(fs::canonicalize(file)?, name)
+}
+
+#[cfg(not(unix))]
+fn set_attribute(_file: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are not supported",
+    ))
+}
+
+#[cfg(not(unix))]
+fn get_attribute(_file: &Path, _name: &str) -> io::Result<Option<Vec<u8>>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are not supported",
+    ))
+}
+
+/// Outcome of scrubbing a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrubStatus {
+    /// Every stored digest matches
+    Ok,
+    /// A digest differs while the modification time is the stored one
+    Corrupted,
+    /// A digest differs and the file was written to since it was stored
+    Modified,
+    /// A sidecar was found without its file
+    Missing,
+    /// Nothing is stored for the file, or it could not be read
+    Unverified,
+}
+
+impl ScrubStatus {
+    /// Compare the `digest` of a file which has `stamp` with its stored checksums
+    pub fn of(stored: &[StoredChecksum], digest: &[u8], stamp: &FileStamp) -> Self {
+        let mismatches: Vec<_> = stored
+            .iter()
+            .filter(|stored| stored.digest != digest)
+            .collect();
+        if mismatches.is_empty() {
+            ScrubStatus::Ok
+        } else if mismatches.iter().any(|stored| stored.mtime == stamp.mtime) {
+            ScrubStatus::Corrupted
+        } else {
+            ScrubStatus::Modified
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ScrubStatus::Ok => "OK",
+            ScrubStatus::Corrupted => "CORRUPTED",
+            ScrubStatus::Modified => "MODIFIED",
+            ScrubStatus::Missing => "MISSING",
+            ScrubStatus::Unverified => "UNVERIFIED",
+        }
+    }
+}
+
+/// Number of files of each status
+#[derive(Debug, Default)]
+pub struct ScrubSummary {
+    pub ok: usize,
+    pub corrupted: usize,
+    pub modified: usize,
+    pub missing: usize,
+    pub unverified: usize,
+}
+
+impl ScrubSummary {
+    pub fn add(&mut self, status: ScrubStatus) {
+        match status {
+            ScrubStatus::Ok => self.ok += 1,
+            ScrubStatus::Corrupted => self.corrupted += 1,
+            ScrubStatus::Modified => self.modified += 1,
+            ScrubStatus::Missing => self.missing += 1,
+            ScrubStatus::Unverified => self.unverified += 1,
+        }
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "scrubbed {} file(s): {} ok, {} corrupted, {} modified, {} missing, {} unverified",
+            self.ok + self.corrupted + self.modified + self.missing + self.unverified,
+            self.ok,
+            self.corrupted,
+            self.modified,
+            self.missing,
+            self.unverified
+        )
+    }
+}
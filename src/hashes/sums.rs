@@ -0,0 +1,172 @@
+use super::encoding::Encoding;
+use crate::utils::io::bytes_path;
+use std::io::BufRead;
+use std::path::PathBuf;
+
+/// A single line of a coreutils-style checksum file,
+/// both text (`<digest>  <path>`) and binary (`<digest> *<path>`) lines are accepted
+#[derive(Debug)]
+pub struct SumEntry {
+    /// Expected digest bytes
+    pub digest: Vec<u8>,
+    /// The file the digest belongs to
+    pub path: PathBuf,
+}
+
+/// Parsed content of a checksum file
+#[derive(Debug, Default)]
+pub struct SumsFile {
+    pub entries: Vec<SumEntry>,
+    /// Number of lines that could not be parsed
+    pub malformed: usize,
+}
+
+/// Undo the escaping `sha256sum` applies to names containing `\` or newlines
+pub fn unescape_path(path: &str) -> Option<String> {
+    let mut result = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => result.push('\\'),
+                'n' => result.push('\n'),
+                'r' => result.push('\r'),
+                _ => return None,
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    Some(result)
+}
+
+/// Same as `unescape_path` on raw bytes, for names that need not be UTF-8
+pub fn unescape_bytes(path: &[u8]) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(path.len());
+    let mut bytes = path.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == b'\\' {
+            match bytes.next()? {
+                b'\\' => result.push(b'\\'),
+                b'n' => result.push(b'\n'),
+                b'r' => result.push(b'\r'),
+                _ => return None,
+            }
+        } else {
+            result.push(byte);
+        }
+    }
+    Some(result)
+}
+
+/// Parse a `<digest>  <path>` or `<digest> *<path>` line
+pub fn parse_line(line: &str, encoding: Encoding) -> Option<SumEntry> {
+    parse_line_bytes(line.as_bytes(), encoding)
+}
+
+/// Same as `parse_line` on raw bytes, `sha256sum` writes names that are not UTF-8 as they are
+pub fn parse_line_bytes(line: &[u8], encoding: Encoding) -> Option<SumEntry> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let space = line.iter().position(|&byte| byte == b' ')?;
+    let digest = encoding.decode(std::str::from_utf8(&line[..space]).ok()?)?;
+    let rest = &line[space + 1..];
+    // text mode is marked with a space, binary mode with `*`
+    let path = rest
+        .strip_prefix(b" ")
+        .or_else(|| rest.strip_prefix(b"*"))?;
+    if digest.is_empty() || path.is_empty() {
+        return None;
+    }
+    let path = if escaped {
+        unescape_bytes(path)?
+    } else {
+        path.to_vec()
+    };
+    Some(SumEntry {
+        digest,
+        path: bytes_path(path),
+    })
+}
+
+/// Read all entries of a checksum file, blank lines and `#` comments are skipped
+pub fn parse_sums<R: BufRead>(reader: R, encoding: Encoding) -> Result<SumsFile, failure::Error> {
+    let mut sums = SumsFile::default();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line, encoding) {
+            Some(entry) => sums.entries.push(entry),
+            None => sums.malformed += 1,
+        }
+    }
+    Ok(sums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+    #[test]
+    fn unescape_path_undoes_escapes() {
+        assert_eq!(unescape_path(r"a\\b\nc\rd").as_deref(), Some("a\\b\nc\rd"));
+        assert_eq!(unescape_path("plain").as_deref(), Some("plain"));
+        assert_eq!(unescape_path(r"bad\t"), None);
+        assert_eq!(unescape_path("trailing\\"), None);
+    }
+
+    #[test]
+    fn unescape_bytes_keeps_invalid_utf8() {
+        assert_eq!(unescape_bytes(b"\xff\\n"), Some(b"\xff\n".to_vec()));
+        assert_eq!(unescape_bytes(b"\xff\\x"), None);
+    }
+
+    #[test]
+    fn parse_line_reads_text_and_binary_lines() {
+        let entry = parse_line(&format!("{}  some file", DIGEST), Encoding::Hex).unwrap();
+        assert_eq!(Encoding::Hex.encode(&entry.digest), DIGEST);
+        assert_eq!(entry.path, PathBuf::from("some file"));
+        let entry = parse_line(&format!("{} *bin", DIGEST), Encoding::Hex).unwrap();
+        assert_eq!(entry.path, PathBuf::from("bin"));
+    }
+
+    #[test]
+    fn parse_line_unescapes_marked_lines() {
+        let entry = parse_line(&format!("\\{}  a\\nb", DIGEST), Encoding::Hex).unwrap();
+        assert_eq!(entry.path, PathBuf::from("a\nb"));
+        // without the leading backslash the name is taken as it is
+        let entry = parse_line(&format!("{}  a\\nb", DIGEST), Encoding::Hex).unwrap();
+        assert_eq!(entry.path, PathBuf::from("a\\nb"));
+    }
+
+    #[test]
+    fn parse_line_rejects_malformed_lines() {
+        for line in &[
+            "",
+            DIGEST,
+            &format!("{} file", DIGEST),
+            &format!("{}  ", DIGEST),
+            "xyz  file",
+            "  file",
+            &format!("\\{}  bad\\q", DIGEST),
+        ] {
+            assert!(parse_line(line, Encoding::Hex).is_none(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn parse_sums_counts_malformed_lines() {
+        let text = format!("# comment\n\n{}  a\r\nnot a line\n{} *b\n", DIGEST, DIGEST);
+        let sums = parse_sums(text.as_bytes(), Encoding::Hex).unwrap();
+        let paths: Vec<_> = sums.entries.iter().map(|entry| &entry.path).collect();
+        assert_eq!(paths, [&PathBuf::from("a"), &PathBuf::from("b")]);
+        assert_eq!(sums.malformed, 1);
+    }
+}
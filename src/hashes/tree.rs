@@ -0,0 +1,227 @@
+//! Merkle tree digests of directory trees.
+//!
+//! A file node's digest is the digest of its content, a directory node's digest is
+//! the digest of its children, each written as kind, optional mode, name and digest
+//! in name order. Two trees with the same root digest have the same names, layout
+//! and contents, and a changed file is found by following the differing subtrees.
+use super::hasher::{Checksum, Hasher};
+use crate::utils::errors::ReadError;
+use crate::utils::filter::{FileFilter, IgnoreRules};
+use crate::utils::progress_read::{prepare_multi_bar, ProgressRead};
+use crate::utils::walk::{self, VisitedDirs};
+use rayon::prelude::*;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    File,
+    Dir,
+}
+
+#[derive(Debug)]
+pub struct TreeNode {
+    /// Path relative to the root of the tree, empty for the root itself
+    pub path: PathBuf,
+    pub kind: NodeKind,
+    /// Permission bits of the node
+    pub mode: u32,
+    /// Content digest for files, digest of the children for directories
+    pub digest: Vec<u8>,
+    /// Children sorted by name, always empty for files
+    pub children: Vec<TreeNode>,
+}
+
+#[cfg(unix)]
+fn mode_of(meta: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode_of(meta: &std::fs::Metadata) -> u32 {
+    if meta.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    name.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+impl TreeNode {
+    fn new(path: PathBuf, kind: NodeKind, meta: &std::fs::Metadata) -> Self {
+        Self {
+            path,
+            kind,
+            mode: mode_of(meta),
+            digest: vec![],
+            children: vec![],
+        }
+    }
+
+    /// The last component of the node path, as stored by the file system
+    pub fn name(&self) -> Vec<u8> {
+        self.path.file_name().map(name_bytes).unwrap_or_default()
+    }
+
+    /// Path of the node on disk, the root node is `root` itself
+    pub fn full_path(&self, root: &Path) -> PathBuf {
+        if self.path.as_os_str().is_empty() {
+            root.to_path_buf()
+        } else {
+            root.join(&self.path)
+        }
+    }
+
+    /// Every node of the tree in pre-order, children in name order
+    pub fn nodes(&self) -> Vec<&TreeNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.nodes());
+        }
+        nodes
+    }
+
+    /// Every file node of the tree, in the same order as `nodes`
+    pub fn files_mut(&mut self) -> Vec<&mut TreeNode> {
+        match self.kind {
+            NodeKind::File => vec![self],
+            NodeKind::Dir => self
+                .children
+                .iter_mut()
+                .flat_map(|child| child.files_mut())
+                .collect(),
+        }
+    }
+
+    /// Compute the digest of every directory once all file digests are set
+    pub fn seal<C: Checksum, F: Fn() -> C>(&mut self, include_mode: bool, new_hasher: &F) {
+        if self.kind == NodeKind::File {
+            return;
+        }
+        let mut hasher = new_hasher();
+        for child in &mut self.children {
+            child.seal(include_mode, new_hasher);
+            hasher.update(match child.kind {
+                NodeKind::File => b"f",
+                NodeKind::Dir => b"d",
+            });
+            if include_mode {
+                hasher.update(&child.mode.to_be_bytes());
+            }
+            let name = child.name();
+            hasher.update(&(name.len() as u64).to_be_bytes());
+            hasher.update(&name);
+            hasher.update(&child.digest);
+        }
+        self.digest = hasher.finish().as_ref().to_vec();
+    }
+}
+
+/// Read the layout of a tree without hashing anything.
+/// Symbolic links are treated as `io::set_symlink_policy` tells, by default they
+/// are not followed and are left out of the tree; links that are not followed
+/// are file nodes.
+/// Files and directories not selected by `filter` are left out.
+pub fn scan_tree_filtered(root: &Path, filter: &FileFilter) -> Result<TreeNode, failure::Error> {
+    let meta = std::fs::metadata(root).map_err(|err| ReadError::MetadataError { err })?;
+    if meta.is_file() {
+        return Ok(TreeNode::new(PathBuf::new(), NodeKind::File, &meta));
+    }
+    let mut node = TreeNode::new(PathBuf::new(), NodeKind::Dir, &meta);
+    let mut visited = VisitedDirs::default();
+    scan_dir(
+        root,
+        &mut node,
+        filter,
+        &IgnoreRules::default(),
+        &mut visited,
+    )?;
+    Ok(node)
+}
+
+fn scan_dir(
+    root: &Path,
+    node: &mut TreeNode,
+    filter: &FileFilter,
+    parent_rules: &IgnoreRules,
+    visited: &mut VisitedDirs,
+) -> Result<(), failure::Error> {
+    let full_dir = root.join(&node.path);
+    if !visited.first_visit(&full_dir) {
+        return Ok(());
+    }
+    let rules = filter.enter_dir(&full_dir, parent_rules)?;
+    let entries = std::fs::read_dir(&full_dir).map_err(|err| ReadError::ReadDirError {
+        err,
+        dir: full_dir.to_string_lossy().to_string(),
+    })?;
+    for entry in entries {
+        let entry = entry?;
+        let meta = match walk::entry_metadata(&entry)? {
+            Some(meta) => meta,
+            None => continue,
+        };
+        let path = node.path.join(entry.file_name());
+        let full_path = entry.path();
+        if meta.is_dir() {
+            if !filter.accepts_dir(&path, &full_path, &rules) {
+                continue;
+            }
+            let mut child = TreeNode::new(path, NodeKind::Dir, &meta);
+            scan_dir(root, &mut child, filter, &rules, visited)?;
+            node.children.push(child);
+        } else if walk::is_walked_file(&meta)
+            && filter.accepts_file(&path, &full_path, &meta, &rules)
+        {
+            node.children
+                .push(TreeNode::new(path, NodeKind::File, &meta));
+        }
+    }
+    node.children.sort_by_cached_key(|child| child.name());
+    Ok(())
+}
+
+/// Build the Merkle tree of a directory (or a single file), leaving out what
+/// `filter` does not select. Files are hashed in parallel on the rayon pool, each
+/// with its own progress bar unless `silent`
+pub fn hash_tree<C: Checksum, F: Fn() -> C + Sync>(
+    root: &Path,
+    filter: &FileFilter,
+    include_mode: bool,
+    new_hasher: &F,
+    silent: bool,
+) -> Result<TreeNode, failure::Error> {
+    let mut tree = scan_tree_filtered(root, filter)?;
+    let mut files = tree.files_mut();
+    let (pbs, multi_bar_thread) = prepare_multi_bar(files.len(), silent);
+    // every file is read even after a failure, so that each bar is finished
+    let results: Vec<_> = files
+        .par_iter_mut()
+        .zip(pbs)
+        .map(|(node, pb)| {
+            let path = node.full_path(root);
+            let digest = ProgressRead::from_file_path(&path, pb, silent)
+                .and_then(|input| new_hasher().from_input(input))
+                .map_err(|err| failure::format_err!("{}: {}", path.display(), err))?;
+            node.digest = digest.as_ref().to_vec();
+            Ok(())
+        })
+        .collect();
+    multi_bar_thread.join().unwrap();
+    results
+        .into_iter()
+        .collect::<Result<(), failure::Error>>()?;
+    tree.seal(include_mode, new_hasher);
+    Ok(tree)
+}
@@ -0,0 +1,63 @@
+//! Extendable-output functions of the SHA-3 family (FIPS 202 and SP 800-185),
+//! the output length is chosen at runtime.
+use super::hasher::Checksum;
+use tiny_keccak::{CShake, Hasher, Shake};
+
+/// Output length of SHAKE128 when none is given, as in OpenSSL
+pub const SHAKE128_DEFAULT_LENGTH: usize = 16;
+/// Output length of SHAKE256 when none is given, as in OpenSSL
+pub const SHAKE256_DEFAULT_LENGTH: usize = 32;
+
+/// A SHAKE or cSHAKE state producing `output_len` bytes
+pub struct Xof<K> {
+    keccak: K,
+    output_len: usize,
+}
+
+impl Xof<Shake> {
+    pub fn shake128(output_len: usize) -> Self {
+        Self {
+            keccak: Shake::v128(),
+            output_len,
+        }
+    }
+    pub fn shake256(output_len: usize) -> Self {
+        Self {
+            keccak: Shake::v256(),
+            output_len,
+        }
+    }
+}
+
+impl Xof<CShake> {
+    /// cSHAKE128 with function name `name` and customization string `custom`
+    pub fn cshake128(output_len: usize, name: &[u8], custom: &[u8]) -> Self {
+        Self {
+            keccak: CShake::v128(name, custom),
+            output_len,
+        }
+    }
+    /// cSHAKE256 with function name `name` and customization string `custom`
+    pub fn cshake256(output_len: usize, name: &[u8], custom: &[u8]) -> Self {
+        Self {
+            keccak: CShake::v256(name, custom),
+            output_len,
+        }
+    }
+}
+
+impl<K: Hasher + Send> Checksum for Xof<K> {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, buf: &[u8]) {
+        self.keccak.update(buf);
+    }
+    fn finish(self) -> Self::Output {
+        let mut output = vec![0; self.output_len];
+        self.keccak.finalize(&mut output);
+        output
+    }
+    fn output_size(&self) -> usize {
+        self.output_len
+    }
+}
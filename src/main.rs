@@ -1,17 +1,526 @@
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate hex_literal;
-
-use clap::{App, crate_version, AppSettings, Arg, SubCommand};
+use clap::{crate_version, App, AppSettings, Arg, ArgGroup, SubCommand};
 mod args;
-mod ciphers;
 mod config;
 mod handlers;
-mod hashes;
-mod utils;
 
+use crupter::{ciphers, hashes, utils};
+
+use hashes::database::ATTRIBUTES;
+use hashes::encoding::ENCODINGS;
+use hashes::format::OUTPUT_FORMATS;
+use hashes::mtree::DEFAULT_KEYWORDS;
 use std::io::Write;
+use utils::io::IO_MODES;
+
+/// Help of `--io`, shared by every subcommand reading files
+const IO_HELP: &str = "how files are read: read, mmap, or auto to map files of 16 MiB and more. \
+A mapped file truncated while it is read makes the whole process crash with SIGBUS";
+
+/// Build the subcommand of a tool, each tool takes its own arguments
+fn tool_subcommand<'a>(action: &'a config::Command, cpus: &'a str) -> App<'a, 'a> {
+    let subcommand = SubCommand::with_name(action.name.as_str()).about(action.help_msg.as_str());
+    match action.name.as_str() {
+        "hash" => symlink_args(filter_args(subcommand))
+            .arg(
+                Arg::with_name("file")
+                    .index(1)
+                    .value_name("FILE")
+                    .help("The file(s) to be hashed, - for the standard input")
+                    .takes_value(true)
+                    .multiple(true),
+            )
+            .arg(
+                Arg::with_name("files-from")
+                    .long("files-from")
+                    .value_name("FILE")
+                    .help("read more file names from FILE, one per line (- for stdin)")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("null")
+                    .short("0")
+                    .long("null")
+                    .help("file names read with --files-from are separated by NUL characters")
+                    .requires("files-from"),
+            )
+            .arg(
+                Arg::with_name("algorithms")
+                    .short("a")
+                    .long("algorithms")
+                    .value_name("ALGO,...")
+                    .help("Comma separated list of hash algorithms")
+                    .takes_value(true)
+                    .use_delimiter(true)
+                    .default_value("md5,sha1,sha2-256"),
+            )
+            .arg(
+                Arg::with_name("parallels")
+                    .short("j")
+                    .long("parallels")
+                    .default_value(cpus)
+                    .help("Number of parallel jobs."),
+            )
+            .arg(
+                Arg::with_name("silent")
+                    .short("s")
+                    .long("silent")
+                    .help("specify to hide progress bar"),
+            )
+            .arg(
+                Arg::with_name("io")
+                    .long("io")
+                    .value_name("MODE")
+                    .help(IO_HELP)
+                    .takes_value(true)
+                    .possible_values(IO_MODES)
+                    .default_value("read"),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .short("f")
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("output format")
+                    .takes_value(true)
+                    .possible_values(OUTPUT_FORMATS),
+            )
+            .arg(
+                Arg::with_name("encoding")
+                    .short("e")
+                    .long("encoding")
+                    .value_name("ENCODING")
+                    .help("text encoding of digests")
+                    .takes_value(true)
+                    .possible_values(ENCODINGS)
+                    .default_value("hex"),
+            )
+            .arg(
+                Arg::with_name("hmac-key")
+                    .long("hmac-key")
+                    .value_name("KEY")
+                    .help("compute HMAC keyed with the string")
+                    .takes_value(true)
+                    .conflicts_with("hmac-key-file"),
+            )
+            .arg(
+                Arg::with_name("hmac-key-file")
+                    .long("hmac-key-file")
+                    .value_name("PATH")
+                    .help("compute HMAC keyed with the content of the file")
+                    .takes_value(true),
+            ),
+        "audit" => subcommand
+            .alias("diff")
+            .arg(
+                Arg::with_name("old")
+                    .index(1)
+                    .value_name("OLD")
+                    .help("Reference manifest or directory")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("new")
+                    .index(2)
+                    .value_name("NEW")
+                    .help("Manifest or directory to compare with the reference")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("algorithm")
+                    .short("a")
+                    .long("algorithm")
+                    .value_name("ALGO")
+                    .help("Hash algorithm used for directories")
+                    .takes_value(true)
+                    .default_value("sha2-256"),
+            )
+            .arg(
+                Arg::with_name("parallels")
+                    .short("j")
+                    .long("parallels")
+                    .default_value(cpus)
+                    .help("Number of parallel jobs."),
+            )
+            .arg(
+                Arg::with_name("silent")
+                    .short("s")
+                    .long("silent")
+                    .help("specify to hide progress bar"),
+            )
+            .arg(
+                Arg::with_name("io")
+                    .long("io")
+                    .value_name("MODE")
+                    .help(IO_HELP)
+                    .takes_value(true)
+                    .possible_values(IO_MODES)
+                    .default_value("read"),
+            )
+            .arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .help("print the report as JSON"),
+            )
+            .arg(
+                Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose")
+                    .help("list unchanged entries as well"),
+            )
+            .arg(
+                Arg::with_name("encoding")
+                    .short("e")
+                    .long("encoding")
+                    .value_name("ENCODING")
+                    .help("text encoding of digests in manifests")
+                    .takes_value(true)
+                    .possible_values(ENCODINGS)
+                    .default_value("hex"),
+            ),
+        "dupes" => filter_args(subcommand)
+            .arg(
+                Arg::with_name("path")
+                    .index(1)
+                    .value_name("PATH")
+                    .help("Files and directories to search")
+                    .multiple(true)
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("algorithm")
+                    .short("a")
+                    .long("algorithm")
+                    .value_name("ALGO")
+                    .help("Hash algorithm used to compare contents")
+                    .takes_value(true)
+                    .default_value("blake3"),
+            )
+            .arg(
+                Arg::with_name("parallels")
+                    .short("j")
+                    .long("parallels")
+                    .default_value(cpus)
+                    .help("Number of parallel jobs."),
+            )
+            .arg(
+                Arg::with_name("silent")
+                    .short("s")
+                    .long("silent")
+                    .help("specify to hide progress bar"),
+            )
+            .arg(
+                Arg::with_name("io")
+                    .long("io")
+                    .value_name("MODE")
+                    .help(IO_HELP)
+                    .takes_value(true)
+                    .possible_values(IO_MODES)
+                    .default_value("read"),
+            )
+            .arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .help("print the report as JSON"),
+            )
+            .arg(
+                Arg::with_name("encoding")
+                    .short("e")
+                    .long("encoding")
+                    .value_name("ENCODING")
+                    .help("text encoding of digests")
+                    .takes_value(true)
+                    .possible_values(ENCODINGS)
+                    .default_value("hex"),
+            )
+            .arg(
+                Arg::with_name("hardlink")
+                    .long("hardlink")
+                    .help("replace duplicates with hard links to the first file of their set")
+                    .conflicts_with("delete"),
+            )
+            .arg(
+                Arg::with_name("delete")
+                    .long("delete")
+                    .help("delete duplicates, keeping the first file of each set"),
+            )
+            .arg(
+                Arg::with_name("yes")
+                    .short("y")
+                    .long("yes")
+                    .help("delete without asking for confirmation")
+                    .requires("delete"),
+            ),
+        "db" => subcommand
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                db_subcommand(
+                    "init",
+                    "Record every file below DIR into a new database.",
+                    cpus,
+                )
+                .arg(
+                    Arg::with_name("algorithms")
+                        .short("a")
+                        .long("algorithms")
+                        .value_name("ALGO,...")
+                        .help("Comma separated list of hash algorithms")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .default_value("sha2-256"),
+                )
+                .arg(
+                    Arg::with_name("attributes")
+                        .long("attributes")
+                        .value_name("ATTR,...")
+                        .help("Comma separated list of recorded attributes")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .possible_values(ATTRIBUTES)
+                        .default_value("size,mode,owner,mtime"),
+                ),
+            )
+            .subcommand(db_subcommand(
+                "check",
+                "Report the files added, removed or changed since the database was recorded.",
+                cpus,
+            ))
+            .subcommand(db_subcommand(
+                "update",
+                "Report the changes and record the current state of the tree.",
+                cpus,
+            )),
+        "mtree" => subcommand
+            .arg(
+                Arg::with_name("dir")
+                    .index(1)
+                    .value_name("DIR")
+                    .help("The tree to describe or verify")
+                    .default_value("."),
+            )
+            .arg(
+                Arg::with_name("file")
+                    .short("f")
+                    .long("file")
+                    .value_name("SPEC")
+                    .help("verify the tree against the specification SPEC instead of printing one")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("keywords")
+                    .short("k")
+                    .long("keywords")
+                    .value_name("KEYWORD,...")
+                    .help("Comma separated list of keywords written to the specification")
+                    .takes_value(true)
+                    .use_delimiter(true)
+                    .default_value(DEFAULT_KEYWORDS),
+            )
+            .arg(
+                Arg::with_name("parallels")
+                    .short("j")
+                    .long("parallels")
+                    .default_value(cpus)
+                    .help("Number of parallel jobs."),
+            )
+            .arg(
+                Arg::with_name("silent")
+                    .short("s")
+                    .long("silent")
+                    .help("specify to hide progress bar"),
+            )
+            .arg(
+                Arg::with_name("io")
+                    .long("io")
+                    .value_name("MODE")
+                    .help(IO_HELP)
+                    .takes_value(true)
+                    .possible_values(IO_MODES)
+                    .default_value("read"),
+            ),
+        "git-hash" => subcommand
+            .arg(
+                Arg::with_name("path")
+                    .index(1)
+                    .value_name("PATH")
+                    .help(
+                        "Files hashed as blobs and directories as trees, - for the standard input",
+                    )
+                    .multiple(true)
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("object-format")
+                    .long("object-format")
+                    .value_name("FORMAT")
+                    .help("hash function of the repository object IDs")
+                    .takes_value(true)
+                    .possible_values(&["sha1", "sha256"])
+                    .default_value("sha1"),
+            )
+            .arg(
+                Arg::with_name("parallels")
+                    .short("j")
+                    .long("parallels")
+                    .default_value(cpus)
+                    .help("Number of parallel jobs."),
+            ),
+        _ => subcommand,
+    }
+}
+
+/// Arguments shared by the subcommands of the integrity database
+fn db_subcommand<'a>(name: &'a str, about: &'a str, cpus: &'a str) -> App<'a, 'a> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(
+            Arg::with_name("database")
+                .index(1)
+                .value_name("DATABASE")
+                .help("The database file")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("dir")
+                .index(2)
+                .value_name("DIR")
+                .help("The tree recorded in the database, by default the one it was created for"),
+        )
+        .arg(
+            Arg::with_name("parallels")
+                .short("j")
+                .long("parallels")
+                .default_value(cpus)
+                .help("Number of parallel jobs."),
+        )
+        .arg(
+            Arg::with_name("silent")
+                .short("s")
+                .long("silent")
+                .help("specify to hide progress bar"),
+        )
+        .arg(
+            Arg::with_name("io")
+                .long("io")
+                .value_name("MODE")
+                .help(IO_HELP)
+                .takes_value(true)
+                .possible_values(IO_MODES)
+                .default_value("read"),
+        )
+        .arg(
+            Arg::with_name("hmac-key")
+                .long("hmac-key")
+                .value_name("KEY")
+                .help("sign the database with HMAC keyed with the string")
+                .takes_value(true)
+                .conflicts_with("hmac-key-file"),
+        )
+        .arg(
+            Arg::with_name("hmac-key-file")
+                .long("hmac-key-file")
+                .value_name("PATH")
+                .help("sign the database with HMAC keyed with the content of the file")
+                .takes_value(true),
+        )
+}
+
+/// File selection arguments of the subcommands that hash or encrypt several files
+fn filter_args<'a>(subcommand: App<'a, 'a>) -> App<'a, 'a> {
+    subcommand
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .value_name("GLOB")
+                .help("only select files whose path or name matches the pattern, repeatable")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .help("leave out files and directories whose path or name matches, repeatable")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("min-size")
+                .long("min-size")
+                .value_name("SIZE")
+                .help("leave out files smaller than SIZE bytes, K, M, G and T suffixes allowed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("newer-than")
+                .long("newer-than")
+                .value_name("TIME")
+                .help("only select files modified after TIME: a UTC date, an age (7d) or a file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-ignore")
+                .long("no-ignore")
+                .help("do not honour .crupterignore files in directories"),
+        )
+}
+
+/// How the subcommands that hash files treat symbolic links
+fn symlink_args<'a>(subcommand: App<'a, 'a>) -> App<'a, 'a> {
+    subcommand
+        .arg(
+            Arg::with_name("follow-symlinks")
+                .short("L")
+                .long("follow-symlinks")
+                .help("follow symbolic links, also those met in directories")
+                .conflicts_with("no-follow"),
+        )
+        .arg(
+            Arg::with_name("no-follow")
+                .short("P")
+                .long("no-follow")
+                .help("never follow symbolic links, hash the path they point to instead"),
+        )
+}
+
+/// Extendable-output functions take an output length, cSHAKE its parameters as well
+fn xof_args<'a>(name: &str, subcommand: App<'a, 'a>) -> App<'a, 'a> {
+    if !name.contains("shake") {
+        return subcommand;
+    }
+    let subcommand = subcommand.arg(
+        Arg::with_name("output-length")
+            .short("l")
+            .long("output-length")
+            .value_name("LENGTH")
+            .help(
+                "output length in bytes, or in bits with a \"bits\" suffix (e.g. 512bits); \
+                 --length is the number of input bytes to hash",
+            )
+            .takes_value(true),
+    );
+    if !name.starts_with("cshake") {
+        return subcommand;
+    }
+    subcommand
+        .arg(
+            Arg::with_name("function-name")
+                .short("N")
+                .long("function-name")
+                .value_name("NAME")
+                .help("cSHAKE function name")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("customization")
+                .short("S")
+                .long("customization")
+                .value_name("STRING")
+                .help("cSHAKE customization string")
+                .takes_value(true),
+        )
+}
 
 fn main() -> Result<(), failure::Error> {
     let config = config::init();
@@ -23,17 +532,32 @@ fn main() -> Result<(), failure::Error> {
     let cpus = num_cpus::get().to_string();
 
     for action in &config.checksums {
-        app = app.subcommand(
+        let subcommand = xof_args(
+            &action.name,
             SubCommand::with_name(action.name.as_str())
                 .about(action.help_msg.as_str())
                 .arg(
                     Arg::with_name("file")
                         .index(1)
                         .value_name("FILE")
-                        .help("The file(s) to be encrypted")
+                        .help("The file(s) to be hashed, - for the standard input")
                         .takes_value(true)
                         .multiple(true),
                 )
+                .arg(
+                    Arg::with_name("files-from")
+                        .long("files-from")
+                        .value_name("FILE")
+                        .help("read more file names from FILE, one per line (- for stdin)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("null")
+                        .short("0")
+                        .long("null")
+                        .help("file names read with --files-from are separated by NUL characters")
+                        .requires("files-from"),
+                )
                 .arg(
                     Arg::with_name("parallels")
                         .short("j")
@@ -46,11 +570,177 @@ fn main() -> Result<(), failure::Error> {
                         .short("s")
                         .long("silent")
                         .help("specify to hide progress bar"),
+                )
+                .arg(
+                    Arg::with_name("io")
+                        .long("io")
+                        .value_name("MODE")
+                        .help(IO_HELP)
+                        .takes_value(true)
+                        .possible_values(IO_MODES)
+                        .default_value("read"),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .short("c")
+                        .long("check")
+                        .help("read checksums from the FILEs (or stdin) and check them"),
+                )
+                .arg(
+                    Arg::with_name("recursive")
+                        .short("r")
+                        .long("recursive")
+                        .conflicts_with("check")
+                        .help("hash every file under the directories and print a sorted manifest"),
+                )
+                .arg(
+                    Arg::with_name("tree")
+                        .short("t")
+                        .long("tree")
+                        .conflicts_with_all(&["check", "cache"])
+                        .help("print one Merkle tree digest per directory, with -r list every subtree"),
+                )
+                .arg(
+                    Arg::with_name("tree-modes")
+                        .long("tree-modes")
+                        .requires("tree")
+                        .help("include permission bits in the tree digest"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("output format")
+                        .takes_value(true)
+                        .possible_values(OUTPUT_FORMATS),
+                )
+                .arg(
+                    Arg::with_name("encoding")
+                        .short("e")
+                        .long("encoding")
+                        .value_name("ENCODING")
+                        .help("text encoding of digests")
+                        .takes_value(true)
+                        .possible_values(ENCODINGS)
+                        .default_value("hex"),
+                )
+                .arg(
+                    Arg::with_name("hmac-key")
+                        .long("hmac-key")
+                        .value_name("KEY")
+                        .help("compute HMAC keyed with the string")
+                        .takes_value(true)
+                        .conflicts_with("hmac-key-file"),
+                )
+                .arg(
+                    Arg::with_name("hmac-key-file")
+                        .long("hmac-key-file")
+                        .value_name("PATH")
+                        .help("compute HMAC keyed with the content of the file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("cache")
+                        .long("cache")
+                        .value_name("PATH")
+                        .help("reuse digests of files whose size, times and inode are unchanged")
+                        .takes_value(true)
+                        .conflicts_with_all(&["check", "hmac-key", "hmac-key-file"]),
+                )
+                .arg(
+                    Arg::with_name("rehash")
+                        .long("rehash")
+                        .requires("cache")
+                        .help("hash every file again and refresh the cache"),
+                )
+                .arg(
+                    Arg::with_name("block-size")
+                        .short("b")
+                        .long("block-size")
+                        .value_name("SIZE")
+                        .help("also print a digest for every block of SIZE bytes (K, M, G suffixes allowed)")
+                        .takes_value(true)
+                        .conflicts_with_all(&["check", "recursive", "tree", "cache", "format"]),
+                )
+                .arg(
+                    Arg::with_name("compare")
+                        .long("compare")
+                        .conflicts_with_all(&["check", "recursive", "tree", "cache", "block-size"])
+                        .help("read two block lists from the FILEs and print the byte ranges that differ"),
+                )
+                .arg(
+                    Arg::with_name("offset")
+                        .long("offset")
+                        .value_name("SIZE")
+                        .help("skip SIZE bytes at the start of each file")
+                        .takes_value(true)
+                        .conflicts_with_all(&["check", "tree", "cache", "block-size", "compare"]),
+                )
+                .arg(
+                    Arg::with_name("length")
+                        .long("length")
+                        .value_name("SIZE")
+                        .help("hash at most SIZE bytes of each file")
+                        .takes_value(true)
+                        .conflicts_with_all(&["check", "tree", "cache", "block-size", "compare"]),
+                )
+                .arg(
+                    Arg::with_name("checkpoint")
+                        .long("checkpoint")
+                        .value_name("PATH")
+                        .help("periodically save the hasher state of the single FILE to PATH")
+                        .takes_value(true)
+                        .conflicts_with_all(&[
+                            "check", "recursive", "tree", "cache", "block-size", "compare", "offset",
+                            "length", "hmac-key", "hmac-key-file",
+                        ]),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .requires("checkpoint")
+                        .help("continue from the checkpoint if it exists"),
+                )
+                .arg(
+                    Arg::with_name("checkpoint-interval")
+                        .long("checkpoint-interval")
+                        .value_name("SIZE")
+                        .help("bytes hashed between two checkpoints")
+                        .takes_value(true)
+                        .default_value("1G"),
+                )
+                .arg(
+                    Arg::with_name("sidecar")
+                        .long("sidecar")
+                        .help("write the checksum of every file to FILE.<algorithm> next to it")
+                        .conflicts_with_all(&[
+                            "check", "tree", "block-size", "compare", "offset", "length",
+                            "checkpoint",
+                        ]),
+                )
+                .arg(
+                    Arg::with_name("xattr")
+                        .long("xattr")
+                        .help("store the checksum of every file in its user.checksum.<algorithm> attribute")
+                        .conflicts_with_all(&[
+                            "check", "tree", "block-size", "compare", "offset", "length",
+                            "checkpoint",
+                        ]),
+                )
+                .group(ArgGroup::with_name("store").args(&["sidecar", "xattr"]).multiple(true))
+                .arg(
+                    Arg::with_name("scrub")
+                        .long("scrub")
+                        .requires("store")
+                        .conflicts_with_all(&["cache", "format"])
+                        .help("verify the checksums stored with --sidecar or --xattr, flagging files changed with an unchanged mtime"),
                 ),
-        )
+        );
+        app = app.subcommand(symlink_args(filter_args(subcommand)));
     }
     for action in &config.ciphers {
-        app = app.subcommand(
+        app = app.subcommand(filter_args(
             SubCommand::with_name(action.name.as_str())
                 .about(action.help_msg.as_str())
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -71,6 +761,20 @@ fn main() -> Result<(), failure::Error> {
                         .takes_value(true)
                         .multiple(true),
                 )
+                .arg(
+                    Arg::with_name("files-from")
+                        .long("files-from")
+                        .value_name("FILE")
+                        .help("read more file names from FILE, one per line (- for stdin)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("null")
+                        .short("0")
+                        .long("null")
+                        .help("file names read with --files-from are separated by NUL characters")
+                        .requires("files-from"),
+                )
                 .arg(
                     Arg::with_name("output")
                         .short("o")
@@ -100,22 +804,41 @@ fn main() -> Result<(), failure::Error> {
                         .long("silent")
                         .help("specify to hide progress bar"),
                 )
+                .arg(
+                    Arg::with_name("io")
+                        .long("io")
+                        .value_name("MODE")
+                        .help(IO_HELP)
+                        .takes_value(true)
+                        .possible_values(IO_MODES)
+                        .default_value("read"),
+                )
                 .arg(
                     Arg::with_name("list-name")
                         .short("l")
                         .long("list-name")
                         .help("only show file name when decrypting")
                 )
-        )
+        ))
+    }
+
+    for action in &config.tools {
+        app = app.subcommand(tool_subcommand(action, &cpus));
     }
 
     let matches = app.get_matches();
 
     // iterate through all actions to find what to do
-    for action in config.checksums.iter().chain(config.ciphers.iter()) {
+    for action in config
+        .checksums
+        .iter()
+        .chain(config.ciphers.iter())
+        .chain(config.tools.iter())
+    {
         if let Some(matches) = matches.subcommand_matches(&action.name) {
             if let Err(error) = (action.handler)(matches) {
-                writeln!(&mut std::io::stderr(), "{}", error).unwrap()
+                writeln!(&mut std::io::stderr(), "{}", error).unwrap();
+                std::process::exit(1);
             }
             break;
         }
@@ -6,7 +6,7 @@ pub enum ReadError {
     OpenFileError { err: std::io::Error },
     #[fail(display = "can not read metadata: {}", err)]
     MetadataError { err: std::io::Error },
-    #[fail(display = "is not file")]
+    #[fail(display = "is not a regular file, block device or FIFO")]
     NotFileError,
     #[fail(display = "fail to create directory {}: {}", dir, err)]
     CreateDirError { dir: String, err: std::io::Error },
@@ -14,6 +14,11 @@ pub enum ReadError {
     NoHomeDir,
     #[fail(display = "fail to read directory {}: {}", dir, err)]
     ReadDirError { dir: String, err: std::io::Error },
+    #[fail(
+        display = "offset {} is past the end of the file ({} bytes)",
+        offset, size
+    )]
+    OffsetPastEnd { offset: u64, size: u64 },
 }
 
 #[derive(Debug, Fail)]
@@ -29,3 +34,131 @@ pub enum CipherError {
     #[fail(display = "password validation fail")]
     PasswordError,
 }
+
+#[derive(Debug, Fail)]
+pub enum ArgumentError {
+    #[fail(
+        display = "invalid size: {} (expected a number of bytes with an optional K, M, G or T suffix)",
+        value
+    )]
+    InvalidSize { value: String },
+    #[fail(display = "unknown I/O mode: {} (supported: auto, mmap, read)", name)]
+    UnknownIoMode { name: String },
+    #[fail(
+        display = "unknown symlink policy: {} (supported: arguments, follow, no-follow)",
+        name
+    )]
+    UnknownSymlinkPolicy { name: String },
+    #[fail(display = "unknown attribute: {} (supported: {})", name, supported)]
+    UnknownAttribute { name: String, supported: String },
+    #[fail(display = "unknown mtree keyword: {} (supported: {})", name, supported)]
+    UnknownKeyword { name: String, supported: String },
+    #[fail(display = "unknown object format: {} (supported: sha1, sha256)", name)]
+    UnknownObjectFormat { name: String },
+    #[fail(
+        display = "invalid output length: {} (expected bytes, or bits with a \"bits\" suffix)",
+        value
+    )]
+    InvalidOutputLength { value: String },
+    #[fail(display = "no file names read from {}", file)]
+    NoFileNames { file: String },
+    #[fail(display = "invalid pattern {}: {}", pattern, err)]
+    InvalidPattern { pattern: String, err: String },
+    #[fail(
+        display = "invalid time: {} (expected a date such as 2024-01-31, an age such as 12h or 7d, or a file)",
+        value
+    )]
+    InvalidTime { value: String },
+    #[fail(display = "every file given was filtered out")]
+    AllFilesFiltered,
+}
+
+#[derive(Debug, Fail)]
+pub enum ChecksumError {
+    #[fail(
+        display = "WARNING: {} computed checksum(s) did NOT match, {} listed file(s) could not be read",
+        failed, missing
+    )]
+    CheckFailed { failed: usize, missing: usize },
+    #[fail(display = "no properly formatted checksum lines found in {}", file)]
+    NoChecksumLines { file: String },
+    #[fail(display = "{} file(s) could not be hashed", count)]
+    HashFailed { count: usize },
+    #[fail(display = "unknown output format: {} (supported: {})", name, supported)]
+    UnknownFormat { name: String, supported: String },
+    #[fail(
+        display = "unknown digest encoding: {} (supported: {})",
+        name, supported
+    )]
+    UnknownEncoding { name: String, supported: String },
+    #[fail(display = "HMAC is not supported for {}", name)]
+    HmacUnsupported { name: String },
+    #[fail(
+        display = "unknown hash algorithm: {} (supported: {})",
+        name, supported
+    )]
+    UnknownAlgorithm { name: String, supported: String },
+    #[fail(display = "{} entries differ between {} and {}", count, old, new)]
+    AuditFailed {
+        count: usize,
+        old: String,
+        new: String,
+    },
+    #[fail(display = "block lists use different block sizes: {} and {}", old, new)]
+    BlockSizeMismatch { old: u64, new: u64 },
+    #[fail(display = "{} and {} differ in {} byte range(s)", old, new, count)]
+    BlocksDiffer {
+        count: usize,
+        old: String,
+        new: String,
+    },
+    #[fail(
+        display = "{} can not save its state, checkpoints are not supported",
+        name
+    )]
+    StateUnsupported { name: String },
+    #[fail(
+        display = "checkpoint {} belongs to another file, algorithm or version of the file",
+        file
+    )]
+    CheckpointMismatch { file: String },
+    #[fail(display = "checkpoint {} holds an invalid state", file)]
+    InvalidCheckpoint { file: String },
+    #[fail(
+        display = "reading stopped at byte {}: {}, continue with --resume from {}",
+        offset, err, checkpoint
+    )]
+    Interrupted {
+        offset: u64,
+        err: std::io::Error,
+        checkpoint: String,
+    },
+    #[fail(display = "{} duplicate(s) could not be {}", count, action)]
+    DupesActionFailed { count: usize, action: String },
+    #[fail(display = "{} checksum(s) could not be stored", count)]
+    StoreFailed { count: usize },
+    #[fail(
+        display = "scrubbing found {} corrupted and {} missing file(s)",
+        corrupted, missing
+    )]
+    ScrubFailed { corrupted: usize, missing: usize },
+    #[fail(display = "{} is not a valid integrity database", file)]
+    InvalidDatabase { file: String },
+    #[fail(display = "{} already exists, update it instead", file)]
+    DatabaseExists { file: String },
+    #[fail(
+        display = "{} has been tampered with or was signed with another key",
+        file
+    )]
+    DatabaseTampered { file: String },
+    #[fail(display = "{} is signed, its HMAC key is needed to read it", file)]
+    DatabaseSigned { file: String },
+    #[fail(display = "{} file(s) differ from {}", count, file)]
+    DatabaseDrift { count: usize, file: String },
+    #[fail(display = "{} line {} is not a valid mtree entry", file, line)]
+    InvalidSpec { file: String, line: usize },
+    #[fail(display = "{} entries differ from {}", count, file)]
+    SpecMismatch { count: usize, file: String },
+    #[fail(display = "{}: {}", path, err)]
+    GitObjectFailed { path: String, err: String },
+}
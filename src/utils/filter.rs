@@ -0,0 +1,155 @@
+//! Selection of the files to hash or encrypt: glob patterns, size and age limits,
+//! and `.crupterignore` files written in gitignore syntax.
+use crate::utils::errors::ArgumentError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+/// Name of the ignore files honoured while walking directories
+pub const IGNORE_FILE: &str = ".crupterignore";
+
+/// Which files are selected, every file when default
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    /// Files must match one of these patterns when there are any
+    include: Option<GlobSet>,
+    /// Files and directories matching these patterns are left out
+    exclude: Option<GlobSet>,
+    /// Smallest size in bytes of a selected file
+    min_size: Option<u64>,
+    /// Selected files were modified after this time
+    newer_than: Option<SystemTime>,
+    /// Honour the `.crupterignore` files found while walking directories
+    ignore_files: bool,
+}
+
+fn build_globs(patterns: &[String]) -> Result<Option<GlobSet>, failure::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|err| ArgumentError::InvalidPattern {
+            pattern: pattern.clone(),
+            err: err.kind().to_string(),
+        })?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Patterns are matched against the whole path and against the file name alone
+fn globs_match(globs: &GlobSet, path: &Path) -> bool {
+    globs.is_match(path) || path.file_name().is_some_and(|name| globs.is_match(name))
+}
+
+impl FileFilter {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        min_size: Option<u64>,
+        newer_than: Option<SystemTime>,
+        ignore_files: bool,
+    ) -> Result<Self, failure::Error> {
+        Ok(Self {
+            include: build_globs(include)?,
+            exclude: build_globs(exclude)?,
+            min_size,
+            newer_than,
+            ignore_files,
+        })
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude
+            .as_ref()
+            .is_some_and(|globs| globs_match(globs, path))
+    }
+
+    /// Rules for the entries of `dir`, which are those of its parent `rules`
+    /// and of the ignore file of `dir` if it has one
+    pub fn enter_dir(
+        &self,
+        dir: &Path,
+        rules: &IgnoreRules,
+    ) -> Result<IgnoreRules, failure::Error> {
+        let ignore_file = dir.join(IGNORE_FILE);
+        if !self.ignore_files || !ignore_file.is_file() {
+            return Ok(rules.clone());
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(err) = builder.add(&ignore_file) {
+            Err(err)?;
+        }
+        let mut rules = rules.clone();
+        rules.0.push(Rc::new(builder.build()?));
+        Ok(rules)
+    }
+
+    /// Whether to walk into the directory `full`, shown as `relative`
+    pub fn accepts_dir(&self, relative: &Path, full: &Path, rules: &IgnoreRules) -> bool {
+        !self.is_excluded(relative) && !rules.is_ignored(full, true)
+    }
+
+    /// Whether to select the file `full` with metadata `meta`, shown as `relative`
+    pub fn accepts_file(
+        &self,
+        relative: &Path,
+        full: &Path,
+        meta: &Metadata,
+        rules: &IgnoreRules,
+    ) -> bool {
+        if let Some(include) = &self.include {
+            if !globs_match(include, relative) {
+                return false;
+            }
+        }
+        if self.is_excluded(relative) || rules.is_ignored(full, false) {
+            return false;
+        }
+        if self.min_size.is_some_and(|min_size| meta.len() < min_size) {
+            return false;
+        }
+        match (self.newer_than, meta.modified()) {
+            (Some(newer_than), Ok(modified)) => modified > newer_than,
+            (Some(_), Err(_)) => false,
+            (None, _) => true,
+        }
+    }
+
+    /// Files given on the command line that pass the filter. The standard input,
+    /// directories and files that can not be read are kept for the handlers to deal with
+    pub fn select(&self, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        files
+            .into_iter()
+            .filter(|file| match std::fs::metadata(file) {
+                Ok(meta) if meta.is_file() => {
+                    self.accepts_file(file, file, &meta, &IgnoreRules::default())
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+/// Ignore files of a directory and of its parents, the deepest last
+#[derive(Clone, Default)]
+pub struct IgnoreRules(Vec<Rc<Gitignore>>);
+
+impl IgnoreRules {
+    /// The deepest ignore file with a matching rule decides, as in git
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for rules in self.0.iter().rev() {
+            match rules.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,406 @@
+//! File input backends used for hashing and encryption.
+//!
+//! Regular files are read with sequential read-ahead hints, or memory mapped when
+//! asked for, for every file or per file in `auto` mode. A mapped file that is
+//! truncated while it is read raises SIGBUS, which kills the process, so mapping
+//! is never the default. Read buffers come from
+//! a pool so that a run over many files does not allocate a fresh buffer per file.
+use crate::utils::errors::{ArgumentError, ReadError};
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Take, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+/// Size of read buffers and of the chunks taken from mappings
+pub const BUFFER_SIZE: usize = 10485760;
+/// Memory kept by the buffer pool between files
+const POOL_BUDGET: usize = 256 * 1024 * 1024;
+/// Files smaller than this are read in auto mode, mapping them costs more than it saves
+const AUTO_MMAP_MIN: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoMode {
+    /// Map large regular files, read the others
+    Auto,
+    /// Map every non-empty regular file
+    Mmap,
+    /// Always read through a buffer
+    #[default]
+    Read,
+}
+
+pub const IO_MODES: &[&str] = &["auto", "mmap", "read"];
+
+impl FromStr for IoMode {
+    type Err = failure::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "auto" => IoMode::Auto,
+            "mmap" => IoMode::Mmap,
+            "read" => IoMode::Read,
+            _ => Err(ArgumentError::UnknownIoMode { name: s.to_owned() })?,
+        })
+    }
+}
+
+static IO_MODE: AtomicU8 = AtomicU8::new(IoMode::Read as u8);
+
+/// Select the backend of every file opened afterwards
+pub fn set_io_mode(mode: IoMode) {
+    IO_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+fn io_mode() -> IoMode {
+    match IO_MODE.load(Ordering::Relaxed) {
+        mode if mode == IoMode::Mmap as u8 => IoMode::Mmap,
+        mode if mode == IoMode::Auto as u8 => IoMode::Auto,
+        _ => IoMode::Read,
+    }
+}
+
+/// How symbolic links are treated when hashing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Follow links given as arguments, leave out those met while walking directories
+    #[default]
+    Arguments,
+    /// Follow every link
+    Follow,
+    /// Never follow links, the path a link points to is hashed as a string
+    NoFollow,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = failure::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "arguments" => SymlinkPolicy::Arguments,
+            "follow" => SymlinkPolicy::Follow,
+            "no-follow" => SymlinkPolicy::NoFollow,
+            _ => Err(ArgumentError::UnknownSymlinkPolicy { name: s.to_owned() })?,
+        })
+    }
+}
+
+static SYMLINK_POLICY: AtomicU8 = AtomicU8::new(SymlinkPolicy::Arguments as u8);
+
+/// Select how links are treated by every file opened and directory walked afterwards
+pub fn set_symlink_policy(policy: SymlinkPolicy) {
+    SYMLINK_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+pub fn symlink_policy() -> SymlinkPolicy {
+    match SYMLINK_POLICY.load(Ordering::Relaxed) {
+        policy if policy == SymlinkPolicy::Follow as u8 => SymlinkPolicy::Follow,
+        policy if policy == SymlinkPolicy::NoFollow as u8 => SymlinkPolicy::NoFollow,
+        _ => SymlinkPolicy::Arguments,
+    }
+}
+
+/// Device and inode of a file, which tell apart the names of the same file
+#[cfg(unix)]
+pub fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Buffers returned after use, at most `POOL_BUDGET` bytes worth are kept
+struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+}
+
+lazy_static! {
+    static ref BUFFER_POOL: BufferPool = BufferPool {
+        free: Mutex::new(vec![]),
+    };
+}
+
+/// A read buffer of `BUFFER_SIZE` bytes, handed back to the pool on drop
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+}
+
+/// Take a buffer from the pool, a new one is allocated when the pool is empty
+pub fn buffer() -> PooledBuffer {
+    let buffer = BUFFER_POOL
+        .free
+        .lock()
+        .unwrap()
+        .pop()
+        .unwrap_or_else(|| vec![0; BUFFER_SIZE]);
+    PooledBuffer { buffer }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let mut free = BUFFER_POOL.free.lock().unwrap();
+        // buffers beyond the budget are freed
+        if (free.len() + 1) * BUFFER_SIZE <= POOL_BUDGET {
+            free.push(std::mem::take(&mut self.buffer));
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+/// Part of a file to read: `length` bytes from `offset`, up to the end when None
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub length: Option<u64>,
+}
+
+impl ByteRange {
+    /// Number of bytes of the range within a file of `size` bytes,
+    /// None when the range starts past the end of the file
+    pub fn len_within(self, size: u64) -> Option<u64> {
+        let available = size.checked_sub(self.offset)?;
+        Some(
+            self.length
+                .map_or(available, |length| length.min(available)),
+        )
+    }
+}
+
+/// Whether a file name stands for the standard input
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// The range of the standard input, which can not seek: skipped bytes are read and dropped
+pub fn stdin_range(range: ByteRange) -> io::Result<Take<io::StdinLock<'static>>> {
+    let mut stdin = io::stdin().lock();
+    io::copy(&mut (&mut stdin).take(range.offset), &mut io::sink())?;
+    Ok(stdin.take(range.length.unwrap_or(u64::MAX)))
+}
+
+/// A memory mapped file consumed from `position` to `end`
+pub struct Mapped {
+    map: Mmap,
+    position: usize,
+    end: usize,
+}
+
+impl Mapped {
+    /// The next chunk of at most `BUFFER_SIZE` bytes, empty at the end of the range
+    pub fn next_chunk(&mut self) -> &[u8] {
+        let start = self.position;
+        self.position = (start + BUFFER_SIZE).min(self.end);
+        &self.map[start..self.position]
+    }
+}
+
+impl Read for Mapped {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut rest = &self.map[self.position..self.end];
+        let read_bytes = rest.read(buf)?;
+        self.position += read_bytes;
+        Ok(read_bytes)
+    }
+}
+
+/// An opened file, mapped or read
+pub enum Input {
+    Read(Take<File>),
+    Mapped(Mapped),
+    /// Target path of a symbolic link which is not followed
+    Link(Cursor<Vec<u8>>),
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Read(file) => file.read(buf),
+            Input::Mapped(mapped) => mapped.read(buf),
+            Input::Link(target) => target.read(buf),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn advise_sequential(file: &File) {
+    use std::os::unix::io::AsRawFd;
+    // only a hint, failures are of no consequence
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn advise_sequential(_file: &File) {}
+
+/// Wrap `length` bytes from `offset` of an opened regular file
+/// in the backend of the current I/O mode. Files that fail to map are read instead
+pub fn open_input(mut file: File, offset: u64, length: u64) -> io::Result<Input> {
+    let map = match io_mode() {
+        IoMode::Mmap => length > 0,
+        IoMode::Auto => length >= AUTO_MMAP_MIN,
+        IoMode::Read => false,
+    };
+    if map && offset + length <= usize::MAX as u64 {
+        // the mapping is read-only, but a file truncated while it is read
+        // raises SIGBUS instead of a read error, see the module documentation
+        if let Ok(map) = unsafe { Mmap::map(&file) } {
+            #[cfg(unix)]
+            let _ = map.advise(memmap2::Advice::Sequential);
+            return Ok(Input::Mapped(Mapped {
+                map,
+                position: offset as usize,
+                end: (offset + length) as usize,
+            }));
+        }
+    }
+    advise_sequential(&file);
+    if offset > 0 {
+        file.seek(SeekFrom::Start(offset))?;
+    }
+    Ok(Input::Read(file.take(length)))
+}
+
+/// Bytes of a path as the file system stores them
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Path of the bytes given by `path_bytes`
+#[cfg(unix)]
+pub fn bytes_path(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn bytes_path(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Target of a symbolic link as bytes, the way it is stored in the link
+pub fn link_target(path: &Path) -> io::Result<Vec<u8>> {
+    let target = fs::read_link(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Ok(target.into_os_string().into_vec())
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(target.to_string_lossy().into_owned().into_bytes())
+    }
+}
+
+#[cfg(unix)]
+fn is_block_device(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    meta.file_type().is_block_device()
+}
+
+#[cfg(not(unix))]
+fn is_block_device(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_fifo(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    meta.file_type().is_fifo()
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+/// Open the range of a file along with the length of the range, None for FIFOs
+/// which are read up to their end. Regular files go through `open_input`, block
+/// devices are read with their size found by seeking to their end, and a link
+/// not followed under `SymlinkPolicy::NoFollow` gives the path it points to
+pub fn open_range(path: &Path, range: ByteRange) -> Result<(Input, Option<u64>), failure::Error> {
+    let past_end = |size| ReadError::OffsetPastEnd {
+        offset: range.offset,
+        size,
+    };
+    if symlink_policy() == SymlinkPolicy::NoFollow {
+        let meta = fs::symlink_metadata(path).map_err(|err| ReadError::MetadataError { err })?;
+        if meta.file_type().is_symlink() {
+            let target = link_target(path).map_err(|err| ReadError::OpenFileError { err })?;
+            let size = target.len() as u64;
+            let length = range.len_within(size).ok_or_else(|| past_end(size))?;
+            let start = range.offset as usize;
+            let target = target[start..start + length as usize].to_vec();
+            return Ok((Input::Link(Cursor::new(target)), Some(length)));
+        }
+    }
+    let mut file = File::open(path).map_err(|err| ReadError::OpenFileError { err })?;
+    let meta = file
+        .metadata()
+        .map_err(|err| ReadError::MetadataError { err })?;
+    if meta.is_file() {
+        let length = range
+            .len_within(meta.len())
+            .ok_or_else(|| past_end(meta.len()))?;
+        let input = open_input(file, range.offset, length)
+            .map_err(|err| ReadError::OpenFileError { err })?;
+        Ok((input, Some(length)))
+    } else if is_block_device(&meta) {
+        let size = file
+            .seek(SeekFrom::End(0))
+            .map_err(|err| ReadError::MetadataError { err })?;
+        let length = range.len_within(size).ok_or_else(|| past_end(size))?;
+        file.seek(SeekFrom::Start(range.offset))
+            .map_err(|err| ReadError::OpenFileError { err })?;
+        advise_sequential(&file);
+        Ok((Input::Read(file.take(length)), Some(length)))
+    } else if is_fifo(&meta) {
+        // a FIFO can not seek, skipped bytes are read and dropped
+        io::copy(&mut (&mut file).take(range.offset), &mut io::sink())
+            .map_err(|err| ReadError::OpenFileError { err })?;
+        let length = range.length.unwrap_or(u64::MAX);
+        Ok((Input::Read(file.take(length)), None))
+    } else {
+        Err(ReadError::NotFileError)?
+    }
+}
+
+/// Replace the file with `data` through a temporary file next to it,
+/// so that an interrupted write never leaves a truncated file behind
+pub fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+    let mut file = File::create(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
@@ -1,4 +1,7 @@
 pub mod errors;
+pub mod filter;
+pub mod io;
 pub mod mustache;
 pub mod parallel;
 pub mod progress_read;
+pub mod walk;
@@ -1,4 +1,4 @@
-use crate::utils::errors::ReadError;
+use crate::utils::io::{self, ByteRange, Input};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io::Read;
 
@@ -38,8 +38,8 @@ pub fn prepare_multi_bar(
 pub struct ProgressRead<R: Read> {
     /// Underlying Read Object
     inner: R,
-    /// Total bytes to read
-    bytes: u64,
+    /// Total bytes to read, None for FIFOs which are read up to their end
+    bytes: Option<u64>,
     /// The progress bar
     bar: Option<ProgressBar>,
     /// Indicate whether the read has finished,
@@ -49,20 +49,45 @@ pub struct ProgressRead<R: Read> {
     silent: bool,
     // read position
     position: u64,
+    // the bar also counts other parts of the file, progress is added to it
+    shared: bool,
+}
+
+/// Show `bytes` as the length of the bar, labelled with the file name
+pub fn style_bar(bar: &ProgressBar, name: &str, bytes: u64) {
+    bar.set_prefix(name);
+    bar.set_length(bytes);
+    let template =
+        name.to_owned() + " [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}";
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template(&template)
+            .progress_chars("#>-"),
+    );
+}
+
+/// Show a spinner with the bytes read so far, for inputs of unknown length
+pub fn style_spinner(bar: &ProgressBar, name: &str) {
+    bar.set_prefix(name);
+    let template = "{spinner} ".to_owned() + name + " [{elapsed_precise}] {bytes} {msg}";
+    bar.set_style(ProgressStyle::default_spinner().template(&template));
 }
 
 impl<R: Read> ProgressRead<R> {
-    pub fn new(inner: R, bytes: u64, name: &str, mut pb: Option<ProgressBar>, silent: bool) -> Self {
-        if let Some(bar) = &mut pb {
-            bar.set_prefix(name);
-            bar.set_length(bytes);
-            let template = name.to_owned()
-                + " [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}";
-            bar.set_style(
-                ProgressStyle::default_bar()
-                    .template(&template)
-                    .progress_chars("#>-"),
-            );
+    /// Wrap `inner` which holds `bytes` bytes, a spinner is shown instead
+    /// of a bar when the length is unknown
+    pub fn new(
+        inner: R,
+        bytes: Option<u64>,
+        name: &str,
+        pb: Option<ProgressBar>,
+        silent: bool,
+    ) -> Self {
+        if let Some(bar) = &pb {
+            match bytes {
+                Some(bytes) => style_bar(bar, name, bytes),
+                None => style_spinner(bar, name),
+            }
         }
         Self {
             inner,
@@ -71,34 +96,117 @@ impl<R: Read> ProgressRead<R> {
             finished: false,
             silent,
             position: 0,
+            shared: false,
         }
     }
+
+    /// Total bytes expected to be read, None when only the end of the input tells
+    pub fn total_bytes(&self) -> Option<u64> {
+        self.bytes
+    }
+
+    /// Move the progress bar forward after `read_size` more bytes
+    fn advance(&mut self, read_size: usize) {
+        let new_position = self.position + read_size as u64;
+        if self.shared {
+            // the owner of the bar finishes it once every part is read
+            if let Some(bar) = &self.bar {
+                bar.inc(read_size as u64);
+            }
+        } else if !self.finished && !self.silent {
+            let done = match self.bytes {
+                Some(bytes) => new_position >= bytes,
+                None => read_size == 0,
+            };
+            if done {
+                self.finished = true;
+                if let Some(bar) = &self.bar {
+                    bar.finish_with_message("Done.");
+                }
+            } else {
+                if let Some(bar) = &self.bar {
+                    bar.set_position(new_position);
+                }
+            }
+        }
+        self.position = new_position;
+    }
 }
 
-impl ProgressRead<std::io::BufReader<std::fs::File>> {
+impl ProgressRead<Input> {
+    /// Open a file with the backend selected by `io::set_io_mode`, see `io::open_range`
     pub fn from_file_path<P: AsRef<std::path::Path>>(
         path: P,
         pb: Option<ProgressBar>,
         silent: bool,
     ) -> Result<Self, failure::Error> {
-        let file = std::fs::File::open(&path).map_err(|err| ReadError::OpenFileError { err })?;
+        Self::from_file_range(path, ByteRange::default(), pb, silent)
+    }
+
+    /// Open part of a file, the progress bar covers the range only
+    pub fn from_file_range<P: AsRef<std::path::Path>>(
+        path: P,
+        range: ByteRange,
+        pb: Option<ProgressBar>,
+        silent: bool,
+    ) -> Result<Self, failure::Error> {
+        let (input, length) = io::open_range(path.as_ref(), range)?;
         let filename = path.as_ref().file_name().unwrap();
-        let meta = file
-            .metadata()
-            .map_err(|err| ReadError::MetadataError { err })?;
-        if !meta.is_file() {
-            Err(ReadError::NotFileError)?;
-        }
-        let file_size = meta.len();
-        let buf_file = std::io::BufReader::new(file);
         Ok(ProgressRead::new(
-            buf_file,
-            file_size,
-            filename.to_str().unwrap(),
+            input,
+            length,
+            &filename.to_string_lossy(),
             pb,
             silent,
         ))
     }
+
+    /// Open one of several parts of a file read concurrently, the bytes read are
+    /// added to `pb` which is styled and finished by the caller, see `style_bar`
+    pub fn from_file_part<P: AsRef<std::path::Path>>(
+        path: P,
+        range: ByteRange,
+        pb: Option<ProgressBar>,
+        silent: bool,
+    ) -> Result<Self, failure::Error> {
+        let (input, length) = io::open_range(path.as_ref(), range)?;
+        Ok(Self {
+            inner: input,
+            bytes: length,
+            bar: pb,
+            finished: false,
+            silent,
+            position: 0,
+            shared: true,
+        })
+    }
+
+    /// Feed the whole file to `consume` chunk by chunk,
+    /// mapped files are passed without being copied
+    pub fn for_each_chunk<F: FnMut(&[u8])>(&mut self, mut consume: F) -> std::io::Result<()> {
+        let mut buffer = None;
+        loop {
+            let read_size = match &mut self.inner {
+                Input::Mapped(mapped) => {
+                    let chunk = mapped.next_chunk();
+                    consume(chunk);
+                    chunk.len()
+                }
+                Input::Read(_) | Input::Link(_) => {
+                    let buffer = buffer.get_or_insert_with(io::buffer);
+                    let read_size = self.read(buffer)?;
+                    consume(&buffer[..read_size]);
+                    read_size
+                }
+            };
+            if read_size == 0 {
+                return Ok(());
+            }
+            if let Input::Mapped(_) = self.inner {
+                self.advance(read_size);
+            }
+        }
+    }
 }
 
 impl<R: Read> Read for ProgressRead<R> {
@@ -109,27 +217,14 @@ impl<R: Read> Read for ProgressRead<R> {
             }
             err
         })?;
-        let new_position = self.position + read_size as u64;
-        if !self.finished && !self.silent {
-            if new_position >= self.bytes {
-                self.finished = true;
-                if let Some(bar) = &self.bar {
-                    bar.finish_with_message("Done.");
-                }
-            } else {
-                if let Some(bar) = &self.bar {
-                    bar.set_position(new_position);
-                }
-            }
-        }
-        self.position = new_position;
+        self.advance(read_size);
         Ok(read_size)
     }
 }
 
 impl<R: Read> Drop for ProgressRead<R> {
     fn drop(&mut self) {
-        if !self.finished {
+        if !self.finished && !self.shared {
             if let Some(bar) = &self.bar {
                 // error happened else where, clear progress bar here
                 bar.finish_and_clear();
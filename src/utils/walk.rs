@@ -0,0 +1,122 @@
+use crate::utils::errors::ReadError;
+use crate::utils::filter::{FileFilter, IgnoreRules};
+use crate::utils::io::{file_id, symlink_policy, SymlinkPolicy};
+use std::collections::HashSet;
+use std::fs::{DirEntry, Metadata};
+use std::path::{Path, PathBuf};
+
+/// Metadata a directory entry is walked with under the symlink policy, None when
+/// the entry is left out. Links are left out unless followed, or kept as links
+/// under `SymlinkPolicy::NoFollow`; broken links are left out when following
+pub fn entry_metadata(entry: &DirEntry) -> std::io::Result<Option<Metadata>> {
+    let meta = entry.metadata()?;
+    if !meta.file_type().is_symlink() {
+        return Ok(Some(meta));
+    }
+    Ok(match symlink_policy() {
+        SymlinkPolicy::Arguments => None,
+        SymlinkPolicy::Follow => std::fs::metadata(entry.path()).ok(),
+        SymlinkPolicy::NoFollow => Some(meta),
+    })
+}
+
+/// Whether an entry with this metadata is walked as a file: regular files,
+/// and links when they are not followed
+pub fn is_walked_file(meta: &Metadata) -> bool {
+    meta.is_file() || meta.file_type().is_symlink()
+}
+
+/// Directories already walked, so that following links back up the tree ends
+#[derive(Default)]
+pub struct VisitedDirs(HashSet<(u64, u64)>);
+
+impl VisitedDirs {
+    /// Whether the directory `dir` is seen for the first time, always true
+    /// unless links are followed
+    pub fn first_visit(&mut self, dir: &Path) -> bool {
+        if symlink_policy() != SymlinkPolicy::Follow {
+            return true;
+        }
+        match std::fs::metadata(dir).ok().as_ref().and_then(file_id) {
+            Some(id) => self.0.insert(id),
+            None => true,
+        }
+    }
+}
+
+/// Collect every regular file below `root` as paths relative to `root`,
+/// sorted so that walking the same tree always gives the same order.
+/// Symbolic links are treated as `io::set_symlink_policy` tells, by default
+/// they are not followed.
+pub fn walk_files(root: &Path) -> Result<Vec<PathBuf>, failure::Error> {
+    walk_files_filtered(root, &FileFilter::default())
+}
+
+/// Same as `walk_files`, keeping the files selected by `filter` and not
+/// walking into the directories it leaves out
+pub fn walk_files_filtered(
+    root: &Path,
+    filter: &FileFilter,
+) -> Result<Vec<PathBuf>, failure::Error> {
+    let mut files = vec![];
+    let mut visited = VisitedDirs::default();
+    let mut dirs = vec![(PathBuf::new(), IgnoreRules::default())];
+    while let Some((dir, parent_rules)) = dirs.pop() {
+        let full_dir = root.join(&dir);
+        if !visited.first_visit(&full_dir) {
+            continue;
+        }
+        let rules = filter.enter_dir(&full_dir, &parent_rules)?;
+        let entries = std::fs::read_dir(&full_dir).map_err(|err| ReadError::ReadDirError {
+            err,
+            dir: full_dir.to_string_lossy().to_string(),
+        })?;
+        for entry in entries {
+            let entry = entry?;
+            let meta = match entry_metadata(&entry)? {
+                Some(meta) => meta,
+                None => continue,
+            };
+            let relative = dir.join(entry.file_name());
+            let full_path = entry.path();
+            if meta.is_dir() {
+                if filter.accepts_dir(&relative, &full_path, &rules) {
+                    dirs.push((relative, rules.clone()));
+                }
+            } else if is_walked_file(&meta)
+                && filter.accepts_file(&relative, &full_path, &meta, &rules)
+            {
+                files.push(relative);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Regular files directly in `dir` selected by `filter`, hidden files left out, sorted
+pub fn list_files(dir: &Path, filter: &FileFilter) -> Result<Vec<PathBuf>, failure::Error> {
+    let rules = filter.enter_dir(dir, &IgnoreRules::default())?;
+    let entries = std::fs::read_dir(dir).map_err(|err| ReadError::ReadDirError {
+        err,
+        dir: dir.to_string_lossy().to_string(),
+    })?;
+    let mut files = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let full_path = entry.path();
+        if let Some(meta) = entry_metadata(&entry)? {
+            if is_walked_file(&meta)
+                && filter.accepts_file(Path::new(&name), &full_path, &meta, &rules)
+            {
+                files.push(full_path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}